@@ -1,17 +1,78 @@
 use clap::ArgMatches;
+use std::{convert::TryFrom, net::ToSocketAddrs, path::PathBuf};
 
 #[derive(Clone)]
 pub struct AppConfig {
     pub http_target: Box<str>,
+    pub read_only: bool,
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+    /// permissions of unix socket (`unix:/path` target)
+    pub socket_mode: u32,
+    /// trash entries older than that are purged, `0` keeps them forever
+    pub trash_days: u32,
+    /// write tags of changed files to `user.xdg.tags` extended attribute
+    pub xattr: bool,
+    /// serve requests without tokens while none exist on any target
+    pub insecure_no_auth: bool,
 }
 
-impl From<ArgMatches<'_>> for AppConfig {
-    fn from(matches: ArgMatches) -> Self {
-        Self {
+impl AppConfig {
+    /// Unix socket or address reachable only from this machine.
+    pub fn is_local(&self) -> bool {
+        if self.http_target.starts_with("unix:") {
+            return true;
+        }
+
+        match self.http_target.to_socket_addrs() {
+            Ok(mut addrs) => addrs.all(|addr| addr.ip().is_loopback()),
+            Err(_) => false,
+        }
+    }
+
+    /// Whether requests without tokens are served while none exist,
+    /// otherwise they are rejected until a token is created.
+    pub fn allows_anonymous(&self) -> bool {
+        self.insecure_no_auth || self.is_local()
+    }
+}
+
+/// Fails naming the option whose value does not parse, instead of serving
+/// with its default.
+impl TryFrom<ArgMatches<'_>> for AppConfig {
+    type Error = String;
+
+    fn try_from(matches: ArgMatches) -> Result<Self, Self::Error> {
+        Ok(Self {
             http_target: matches
                 .value_of("http_target")
                 .map(|x| x.into())
                 .unwrap_or_else(|| "127.0.0.1:12345".into()),
-        }
+            read_only: matches.is_present("read_only"),
+            tls_cert: matches.value_of_os("tls_cert").map(PathBuf::from),
+            tls_key: matches.value_of_os("tls_key").map(PathBuf::from),
+            socket_mode: value(&matches, "socket_mode", "--socket-mode", |x| {
+                u32::from_str_radix(x, 8).ok()
+            })?
+            .unwrap_or(0o660),
+            trash_days: value(&matches, "trash_days", "--trash-days", |x| x.parse().ok())?
+                .unwrap_or(30),
+            xattr: matches.is_present("xattr"),
+            insecure_no_auth: matches.is_present("insecure_no_auth"),
+        })
+    }
+}
+
+fn value<T>(
+    matches: &ArgMatches,
+    name: &str,
+    option: &str,
+    parse: impl FnOnce(&str) -> Option<T>,
+) -> Result<Option<T>, String> {
+    match matches.value_of(name) {
+        Some(x) => parse(x)
+            .map(Some)
+            .ok_or_else(|| format!("invalid value `{}` of {}", x, option)),
+        None => Ok(None),
     }
 }
@@ -0,0 +1,512 @@
+#[macro_use]
+extern crate log;
+extern crate tagz;
+
+use fuse::{
+    FileAttr, FileType, Filesystem, ReplyAttr, ReplyData, ReplyDirectory, ReplyEmpty, ReplyEntry,
+    Request,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    ffi::OsStr,
+    path::Path,
+};
+use tagz::{library, models, Connection, Library, Session};
+use time::Timespec;
+
+/// How long the kernel may cache entries and attributes.
+const TTL: Timespec = Timespec { sec: 1, nsec: 0 };
+const ROOT: u64 = 1;
+const FILES_PER_PAGE: u32 = 500;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    std::env::set_var("RUST_LOG", "tagz=info,tagz_fuse=info");
+    env_logger::init();
+
+    let matches = {
+        use clap::{App, Arg};
+
+        App::new("tagz-fuse")
+            .about("Mounts TagZ library as directories: `/tags/cat/dog/` holds symlinks to files tagged with both. `mkdir` creates tag, `ln -s` tags file with every tag of the directory, `rm` removes the last one.")
+            .author("Øsystems")
+            .arg(
+                Arg::with_name("mountpoint")
+                    .required(true),
+            )
+            .arg(
+                Arg::with_name("db")
+                    .help("Database of the daemon (default: tagz.db)")
+                    .long("db")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("user")
+                    .help("Acts as the given user, so only entries visible to it are shown")
+                    .short("u")
+                    .long("user")
+                    .takes_value(true),
+            )
+            .get_matches()
+    };
+
+    let connection = tagz::get_conn(Path::new(matches.value_of("db").unwrap_or("tagz.db")))?;
+
+    let session = match matches.value_of("user") {
+        Some(name) => {
+            let user = models::User::find_by_name(name, &connection)?
+                .ok_or_else(|| format!("user `{}` does not exist", name))?;
+
+            Session {
+                user_id: Some(user.id),
+                access: models::Access::User(user.id),
+                admin: user.role >= models::Permission::Admin,
+                actor: user.name,
+            }
+        }
+        None => Session::local("fuse"),
+    };
+
+    let mountpoint = matches.value_of("mountpoint").unwrap();
+
+    info!("Mounting library at {}.", mountpoint);
+
+    fuse::mount(
+        TagFs::new(connection, session),
+        &mountpoint,
+        &[OsStr::new("-o"), OsStr::new("fsname=tagz")],
+    )?;
+
+    Ok(())
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+enum Node {
+    Root,
+    Tags,
+    /// files having every tag of the path
+    Dir(Vec<String>),
+    Link(i32),
+}
+
+/// Inodes are handed out on first sight and kept while mounted.
+#[derive(Default)]
+struct Nodes {
+    nodes: Vec<Node>,
+    inodes: HashMap<Node, u64>,
+}
+
+impl Nodes {
+    fn ino(&mut self, node: Node) -> u64 {
+        if let Some(ino) = self.inodes.get(&node) {
+            return *ino;
+        }
+
+        self.nodes.push(node.clone());
+        let ino = self.nodes.len() as u64;
+        self.inodes.insert(node, ino);
+
+        ino
+    }
+
+    fn get(&self, ino: u64) -> Option<Node> {
+        self.nodes.get(ino.wrapping_sub(1) as usize).cloned()
+    }
+}
+
+struct TagFs {
+    conn: Connection,
+    session: Session,
+    nodes: Nodes,
+    uid: u32,
+    gid: u32,
+    mounted_at: Timespec,
+}
+
+impl TagFs {
+    fn new(conn: Connection, session: Session) -> Self {
+        let mut nodes = Nodes::default();
+        nodes.ino(Node::Root);
+
+        Self {
+            conn,
+            session,
+            nodes,
+            uid: unsafe { libc::getuid() },
+            gid: unsafe { libc::getgid() },
+            mounted_at: time::get_time(),
+        }
+    }
+
+    fn library(&mut self) -> Library<'_> {
+        Library::new(&mut self.conn, self.session.clone())
+    }
+
+    fn attr(&mut self, node: Node) -> library::Result<FileAttr> {
+        let (kind, perm, size, time) = match node {
+            Node::Link(id) => {
+                let file = self.library().file_by_id(id)?;
+
+                (
+                    FileType::Symlink,
+                    0o777,
+                    file.name.len() as u64,
+                    Timespec::new(file.updated_at.timestamp(), 0),
+                )
+            }
+            _ => (FileType::Directory, 0o755, 0, self.mounted_at),
+        };
+
+        Ok(FileAttr {
+            ino: self.nodes.ino(node),
+            size,
+            blocks: 0,
+            atime: time,
+            mtime: time,
+            ctime: time,
+            crtime: time,
+            kind,
+            perm,
+            nlink: 1,
+            uid: self.uid,
+            gid: self.gid,
+            rdev: 0,
+            flags: 0,
+        })
+    }
+
+    /// Subdirectory or file named `name` in `parent`.
+    fn child(&mut self, parent: &Node, name: &str) -> library::Result<Node> {
+        match parent {
+            Node::Root if name == "tags" => Ok(Node::Tags),
+            Node::Tags => {
+                self.library().tag(name)?;
+
+                Ok(Node::Dir(vec![name.to_owned()]))
+            }
+            Node::Dir(tags) => {
+                if !tags.iter().any(|tag| tag == name) && self.library().tag(name).is_ok() {
+                    let mut tags = tags.clone();
+                    tags.push(name.to_owned());
+
+                    return Ok(Node::Dir(tags));
+                }
+
+                links(&self.library(), tags)?
+                    .into_iter()
+                    .find(|(entry, _)| entry == name)
+                    .map(|(_, id)| Node::Link(id))
+                    .ok_or(library::Error::FileNotFound)
+            }
+            _ => Err(library::Error::FileNotFound),
+        }
+    }
+
+    /// Listing of directory: tags of its files to narrow down further and the files themselves.
+    fn children(&mut self, node: &Node) -> library::Result<Vec<(String, Node)>> {
+        Ok(match node {
+            Node::Root => vec![("tags".to_owned(), Node::Tags)],
+            Node::Tags => self
+                .library()
+                .tags()?
+                .into_iter()
+                .map(|tag| (tag.name.clone(), Node::Dir(vec![tag.name])))
+                .collect(),
+            Node::Dir(tags) => {
+                let library = self.library();
+                let names = tags.iter().map(String::as_str).collect::<Vec<_>>();
+                let (_, facets) = library.facets(&library::Query {
+                    tags: &names,
+                    exact: true,
+                    ..Default::default()
+                })?;
+
+                let mut children = facets
+                    .into_iter()
+                    .filter(|facet| !tags.contains(&facet.name))
+                    .map(|facet| {
+                        let mut tags = tags.clone();
+                        tags.push(facet.name.clone());
+
+                        (facet.name, Node::Dir(tags))
+                    })
+                    .collect::<Vec<_>>();
+
+                children.extend(
+                    links(&library, tags)?
+                        .into_iter()
+                        .map(|(entry, id)| (entry, Node::Link(id))),
+                );
+
+                children
+            }
+            Node::Link(_) => Vec::new(),
+        })
+    }
+}
+
+impl Filesystem for TagFs {
+    fn lookup(&mut self, _req: &Request, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        let result = match (self.nodes.get(parent), name.to_str()) {
+            (Some(parent), Some(name)) => self.child(&parent, name),
+            _ => Err(library::Error::FileNotFound),
+        }
+        .and_then(|node| self.attr(node));
+
+        match result {
+            Ok(attr) => reply.entry(&TTL, &attr, 0),
+            Err(err) => reply.error(errno(&err)),
+        }
+    }
+
+    fn getattr(&mut self, _req: &Request, ino: u64, reply: ReplyAttr) {
+        let result = match self.nodes.get(ino) {
+            Some(node) => self.attr(node),
+            None => Err(library::Error::FileNotFound),
+        };
+
+        match result {
+            Ok(attr) => reply.attr(&TTL, &attr),
+            Err(err) => reply.error(errno(&err)),
+        }
+    }
+
+    fn readlink(&mut self, _req: &Request, ino: u64, reply: ReplyData) {
+        match self.nodes.get(ino) {
+            Some(Node::Link(id)) => match self.library().file_by_id(id) {
+                Ok(file) => reply.data(file.name.as_bytes()),
+                Err(err) => reply.error(errno(&err)),
+            },
+            _ => reply.error(libc::EINVAL),
+        }
+    }
+
+    fn readdir(
+        &mut self,
+        _req: &Request,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        mut reply: ReplyDirectory,
+    ) {
+        let node = match self.nodes.get(ino) {
+            Some(node) => node,
+            None => return reply.error(libc::ENOENT),
+        };
+
+        let children = match self.children(&node) {
+            Ok(children) => children,
+            Err(err) => return reply.error(errno(&err)),
+        };
+
+        let parent = match &node {
+            Node::Dir(tags) if tags.len() > 1 => {
+                self.nodes.ino(Node::Dir(tags[..tags.len() - 1].to_vec()))
+            }
+            Node::Dir(_) => self.nodes.ino(Node::Tags),
+            _ => ROOT,
+        };
+
+        let mut entries = vec![
+            (ino, FileType::Directory, ".".to_owned()),
+            (parent, FileType::Directory, "..".to_owned()),
+        ];
+
+        for (name, child) in children {
+            let kind = match child {
+                Node::Link(_) => FileType::Symlink,
+                _ => FileType::Directory,
+            };
+
+            entries.push((self.nodes.ino(child), kind, name));
+        }
+
+        for (i, (ino, kind, name)) in entries.into_iter().enumerate().skip(offset as usize) {
+            if reply.add(ino, (i + 1) as i64, kind, name) {
+                break;
+            }
+        }
+
+        reply.ok();
+    }
+
+    fn mkdir(&mut self, _req: &Request, parent: u64, name: &OsStr, _mode: u32, reply: ReplyEntry) {
+        let result = match (self.nodes.get(parent), name.to_str()) {
+            (Some(Node::Tags), Some(name)) => Ok((Vec::new(), name)),
+            (Some(Node::Dir(tags)), Some(name)) => Ok((tags, name)),
+            (Some(_), _) => Err(libc::EPERM),
+            (None, _) => Err(libc::ENOENT),
+        };
+
+        let (mut tags, name) = match result {
+            Ok(result) => result,
+            Err(err) => return reply.error(err),
+        };
+
+        let result = self.library().create_tag(name, None, true);
+        let result = result.and_then(|tag| {
+            info!("Created tag `{}`.", tag.name);
+
+            tags.push(tag.name);
+            self.attr(Node::Dir(tags))
+        });
+
+        match result {
+            Ok(attr) => reply.entry(&TTL, &attr, 0),
+            Err(err) => reply.error(errno(&err)),
+        }
+    }
+
+    /// Name of the link is ignored, file is known by its target.
+    fn symlink(
+        &mut self,
+        _req: &Request,
+        parent: u64,
+        _name: &OsStr,
+        link: &Path,
+        reply: ReplyEntry,
+    ) {
+        let (tags, name) = match (self.nodes.get(parent), link.to_str()) {
+            (Some(Node::Dir(tags)), Some(name)) => (tags, name),
+            (Some(_), _) => return reply.error(libc::EPERM),
+            (None, _) => return reply.error(libc::ENOENT),
+        };
+
+        let result = link_file(&mut self.library(), name, &tags);
+        let result = result.and_then(|id| {
+            info!("Tagged `{}` with {}.", name, tags.join(", "));
+
+            self.attr(Node::Link(id))
+        });
+
+        match result {
+            Ok(attr) => reply.entry(&TTL, &attr, 0),
+            Err(err) => reply.error(errno(&err)),
+        }
+    }
+
+    /// Removes the tag of directory from the file.
+    fn unlink(&mut self, _req: &Request, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+        let tags = match self.nodes.get(parent) {
+            Some(Node::Dir(tags)) => tags,
+            Some(_) => return reply.error(libc::EPERM),
+            None => return reply.error(libc::ENOENT),
+        };
+
+        let result = match name.to_str() {
+            Some(name) => self.child(&Node::Dir(tags.clone()), name),
+            None => Err(library::Error::FileNotFound),
+        }
+        .and_then(|node| match node {
+            Node::Link(id) => self.library().remove_tag(id, tags.last().unwrap()),
+            _ => Err(library::Error::PermissionDenied),
+        });
+
+        match result {
+            Ok(()) => reply.ok(),
+            Err(err) => reply.error(errno(&err)),
+        }
+    }
+}
+
+/// Creates file with `tags` or adds the missing ones to existing file.
+fn link_file(library: &mut Library, name: &str, tags: &[String]) -> library::Result<i32> {
+    match library.file(name) {
+        Ok(file) => library.atomically(|library| {
+            for tag in tags {
+                if !file.tags.iter().any(|own| &own.name == tag) {
+                    library.add_tag(file.id, tag)?;
+                }
+            }
+
+            Ok(file.id)
+        }),
+        Err(library::Error::FileNotFound) => Ok(library.create_file(name, tags, true)?.id),
+        Err(err) => Err(err),
+    }
+}
+
+fn files(library: &Library, tags: &[String]) -> library::Result<Vec<models::File>> {
+    let names = tags.iter().map(String::as_str).collect::<Vec<_>>();
+    let query = library::Query {
+        tags: &names,
+        exact: true,
+        ..Default::default()
+    };
+
+    let mut files = Vec::new();
+
+    for page in 0.. {
+        let batch = library.files(&query, FILES_PER_PAGE, page)?;
+        let last = batch.len() < FILES_PER_PAGE as usize;
+        files.extend(batch);
+
+        if last {
+            break;
+        }
+    }
+
+    Ok(files)
+}
+
+/// Links to files having every tag of `tags`, see [`entries`].
+fn links(library: &Library, tags: &[String]) -> library::Result<Vec<(String, i32)>> {
+    let taken = library.tags()?.into_iter().map(|tag| tag.name).collect();
+
+    Ok(entries(&files(library, tags)?, taken))
+}
+
+/// Entries named by the last component of file name, ones repeated or already
+/// `taken` by subdirectories get id appended.
+fn entries(files: &[models::File], mut taken: HashSet<String>) -> Vec<(String, i32)> {
+    files
+        .iter()
+        .map(|file| {
+            let base = Path::new(&file.name)
+                .file_name()
+                .and_then(OsStr::to_str)
+                .unwrap_or(&file.name)
+                .replace('/', "_");
+
+            let entry = if taken.contains(&base) {
+                format!("{} ({})", base, file.id)
+            } else {
+                base
+            };
+
+            taken.insert(entry.clone());
+
+            (entry, file.id)
+        })
+        .collect()
+}
+
+fn errno(err: &library::Error) -> i32 {
+    match err {
+        library::Error::FileNotFound
+        | library::Error::TagNotFound
+        | library::Error::TagsNotFound(_)
+        | library::Error::RelationNotFound
+        | library::Error::CollectionNotFound
+        | library::Error::CollectionFileNotFound
+        | library::Error::SearchNotFound
+        | library::Error::TrashNotFound
+        | library::Error::WebhookNotFound => libc::ENOENT,
+        library::Error::FileDuplication
+        | library::Error::TagDuplication
+        | library::Error::RelationExists
+        | library::Error::NameTaken
+        | library::Error::CollectionDuplication
+        | library::Error::CollectionFileExists
+        | library::Error::SearchDuplication
+        | library::Error::WebhookDuplication => libc::EEXIST,
+        library::Error::CollectionOrderMismatch | library::Error::WebhookInvalidUrl => libc::EINVAL,
+        library::Error::ConfirmationRequired => libc::ENOTEMPTY,
+        library::Error::PermissionDenied => libc::EACCES,
+        library::Error::Sql(err) => {
+            error!("{}", err);
+
+            libc::EIO
+        }
+    }
+}
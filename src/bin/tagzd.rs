@@ -2,36 +2,475 @@
 extern crate log;
 extern crate tagz;
 
-use std::error::Error;
-use tagz::AppConfig;
+use clap::ArgMatches;
+use std::{convert::TryFrom, error::Error};
+use tagz::{models, AppConfig, Connection};
 
 #[actix_rt::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    std::env::set_var("RUST_LOG", "tagzd=info,actix_web=info");
+    std::env::set_var("RUST_LOG", "tagz=info,tagzd=info,actix_web=info");
     env_logger::init();
 
     let matches = {
-        use clap::{App, Arg};
+        use clap::{App, AppSettings, Arg, SubCommand};
 
         App::new("tagzd")
             .about("Daemon for TagZ.")
             .author("Øsystems")
+            .setting(AppSettings::VersionlessSubcommands)
             .arg(
                 Arg::with_name("http_target")
-                    .help("Http target (default: 127.0.0.1:12345)")
+                    .help("Http target, `unix:/path/to/sock` for unix socket (default: 127.0.0.1:12345)")
                     .short("t")
                     .long("target")
                     .takes_value(true),
             )
+            .arg(
+                Arg::with_name("socket_mode")
+                    .help("Octal permissions of unix socket (default: 660)")
+                    .long("socket-mode")
+                    .takes_value(true)
+                    .validator(|x| {
+                        u32::from_str_radix(&x, 8)
+                            .map(|_| ())
+                            .map_err(|_| "must be octal, e.g. 660".to_owned())
+                    }),
+            )
+            .arg(
+                Arg::with_name("tls_cert")
+                    .help("PEM certificate chain, serves HTTPS along with --tls-key (reloaded on SIGHUP on unix)")
+                    .long("tls-cert")
+                    .takes_value(true)
+                    .requires("tls_key"),
+            )
+            .arg(
+                Arg::with_name("tls_key")
+                    .help("PEM private key (PKCS#8 or RSA) for --tls-cert")
+                    .long("tls-key")
+                    .takes_value(true)
+                    .requires("tls_cert"),
+            )
+            .arg(
+                Arg::with_name("trash_days")
+                    .help("Days deleted files and tags stay restorable, 0 keeps them forever (default: 30)")
+                    .long("trash-days")
+                    .takes_value(true)
+                    .validator(|x| {
+                        x.parse::<u32>()
+                            .map(|_| ())
+                            .map_err(|_| "must be a number of days".to_owned())
+                    }),
+            )
+            .arg(
+                Arg::with_name("xattr")
+                    .help("Writes tags of changed files to their `user.xdg.tags` extended attribute")
+                    .long("xattr"),
+            )
+            .arg(
+                Arg::with_name("read_only")
+                    .help("Rejects every request changing tags, files, searches or collections")
+                    .long("read-only"),
+            )
+            .arg(
+                Arg::with_name("insecure_no_auth")
+                    .help("Serves requests without tokens while none exist, even on non-local targets")
+                    .long("insecure-no-auth"),
+            )
+            .subcommand(
+                SubCommand::with_name("token")
+                    .about(
+                        "Manages API tokens. Every request must carry one, unless none exist and the target is local.",
+                    )
+                    .setting(AppSettings::SubcommandRequiredElseHelp)
+                    .subcommand(
+                        SubCommand::with_name("create")
+                            .about("Creates token and prints its secret (shown only once).")
+                            .arg(Arg::with_name("name").required(true))
+                            .arg(
+                                Arg::with_name("user")
+                                    .help(
+                                        "User acting with this token (default: none, full access)",
+                                    )
+                                    .short("u")
+                                    .long("user")
+                                    .takes_value(true),
+                            )
+                            .arg(
+                                Arg::with_name("scope")
+                                    .help("What token allows on top of user role (default: admin)")
+                                    .short("s")
+                                    .long("scope")
+                                    .takes_value(true)
+                                    .possible_values(&["read", "write", "admin"]),
+                            ),
+                    )
+                    .subcommand(
+                        SubCommand::with_name("revoke")
+                            .about("Revokes token.")
+                            .arg(Arg::with_name("name").required(true)),
+                    )
+                    .subcommand(SubCommand::with_name("list").about("Lists tokens.")),
+            )
+            .subcommand(
+                SubCommand::with_name("user")
+                    .about("Manages user accounts. Roles: read, tag, admin.")
+                    .setting(AppSettings::SubcommandRequiredElseHelp)
+                    .subcommand(
+                        SubCommand::with_name("create")
+                            .about("Creates user.")
+                            .arg(Arg::with_name("name").required(true))
+                            .arg(
+                                Arg::with_name("role")
+                                    .help("Role of the user (default: read)")
+                                    .short("r")
+                                    .long("role")
+                                    .takes_value(true)
+                                    .possible_values(&["read", "tag", "admin"]),
+                            ),
+                    )
+                    .subcommand(
+                        SubCommand::with_name("role")
+                            .about("Changes role of user.")
+                            .arg(Arg::with_name("name").required(true))
+                            .arg(
+                                Arg::with_name("role")
+                                    .required(true)
+                                    .possible_values(&["read", "tag", "admin"]),
+                            ),
+                    )
+                    .subcommand(
+                        SubCommand::with_name("delete")
+                            .about("Deletes user along with its tokens, owned entries go to another user.")
+                            .arg(Arg::with_name("name").required(true))
+                            .arg(
+                                Arg::with_name("transfer-to")
+                                    .help("User getting owned entries, trashed ones included")
+                                    .short("t")
+                                    .long("transfer-to")
+                                    .takes_value(true)
+                                    .required(true),
+                            ),
+                    )
+                    .subcommand(SubCommand::with_name("list").about("Lists users.")),
+            )
+            .subcommand(
+                SubCommand::with_name("scan")
+                    .about("Imports tags of files under <dir> from their `user.xdg.tags` extended attribute.")
+                    .arg(Arg::with_name("dir").required(true)),
+            )
+            .subcommand(
+                SubCommand::with_name("sidecar")
+                    .about("Syncs tags of files under <dir> with their sidecar files.")
+                    .setting(AppSettings::SubcommandRequiredElseHelp)
+                    .arg(
+                        Arg::with_name("format")
+                            .help("`xmp` for dc:subject of <file>.xmp or <name>.xmp, `json` for <file>.tagz.json (default: xmp)")
+                            .short("f")
+                            .long("format")
+                            .global(true)
+                            .takes_value(true)
+                            .possible_values(&["xmp", "json"]),
+                    )
+                    .subcommand(
+                        SubCommand::with_name("import")
+                            .about("Imports tags from sidecars, missing files and tags are created.")
+                            .arg(Arg::with_name("dir").required(true)),
+                    )
+                    .subcommand(
+                        SubCommand::with_name("export")
+                            .about("Writes tags of known files to sidecars.")
+                            .arg(Arg::with_name("dir").required(true)),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name("import")
+                    .about("Imports files and tags from other tagging tools.")
+                    .setting(AppSettings::SubcommandRequiredElseHelp)
+                    .arg(
+                        Arg::with_name("dry-run")
+                            .help("Reports what would be imported without changing anything")
+                            .short("n")
+                            .long("dry-run")
+                            .global(true),
+                    )
+                    .subcommand(
+                        SubCommand::with_name("tmsu")
+                            .about("Imports a TMSU database, tags with values become <tag>=<value>.")
+                            .arg(Arg::with_name("db").help("Usually .tmsu/db").required(true)),
+                    )
+                    .subcommand(
+                        SubCommand::with_name("hydrus")
+                            .about("Imports files under <dir> exported by Hydrus along with <file>.txt tag files.")
+                            .arg(Arg::with_name("dir").required(true)),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name("materialize")
+                    .about("Builds symlinks to files in <dir>/<tag>/<tag>/..., next runs apply only changes from the audit log.")
+                    .arg(Arg::with_name("dir").required(true))
+                    .arg(
+                        Arg::with_name("depth")
+                            .help("How many tags deep directories go (default: 2)")
+                            .short("d")
+                            .long("depth")
+                            .takes_value(true)
+                            .validator(|x| {
+                                x.parse::<u32>()
+                                    .map(|_| ())
+                                    .map_err(|_| "must be a number".to_owned())
+                            }),
+                    )
+                    .arg(
+                        Arg::with_name("watch")
+                            .help("Keeps following the audit log")
+                            .short("w")
+                            .long("watch"),
+                    ),
+            )
             .get_matches()
     };
 
-    let cfg = AppConfig::from(matches);
-    let connection = tagz::get_conn(std::path::Path::new("tagz.db"))?;
+    let mut connection = tagz::get_conn(std::path::Path::new("tagz.db"))?;
 
     info!("Connection to db file is set.");
 
+    if let Some(matches) = matches.subcommand_matches("token") {
+        return token(matches, &connection);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("user") {
+        return user(matches, &connection);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("scan") {
+        let dir = std::path::Path::new(matches.value_of("dir").unwrap());
+        let tagged = tagz::xattr::scan(
+            dir,
+            &mut tagz::Library::new(&mut connection, tagz::Session::local("scan")),
+        )?;
+
+        info!("Tagged {} files.", tagged);
+
+        return Ok(());
+    }
+
+    if let Some(matches) = matches.subcommand_matches("sidecar") {
+        return sidecar(matches, &mut connection);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("import") {
+        return import(matches, &mut connection);
+    }
+
+    if let Some(matches) = matches.subcommand_matches("materialize") {
+        return materialize(matches, &mut connection);
+    }
+
+    let cfg = AppConfig::try_from(matches)?;
+
+    if cfg.read_only {
+        info!("Running in read-only mode.");
+    }
+
+    if cfg.insecure_no_auth && !cfg.is_local() {
+        warn!("Authentication is disabled while no API tokens exist (--insecure-no-auth), anyone reaching {} gets full access.", cfg.http_target);
+    }
+
+    if !models::Token::any_exists(&connection)? {
+        if cfg.allows_anonymous() {
+            warn!("No API tokens exist, so the API is not protected. Create one with `tagzd token create <name>`.");
+        } else {
+            warn!("No API tokens exist, every request is rejected. Create one with `tagzd token create <name>` or serve a local target.");
+        }
+    }
+
     tagz::serv::run(connection, cfg).await?;
 
     Ok(())
 }
+
+fn token(matches: &ArgMatches, conn: &Connection) -> Result<(), Box<dyn Error>> {
+    match matches.subcommand() {
+        ("create", Some(matches)) => {
+            let name = matches.value_of("name").unwrap();
+
+            if models::Token::find_by_name(name, conn)?.is_some() {
+                return Err(format!("token `{}` already exists", name).into());
+            }
+
+            let user_id = match matches.value_of("user") {
+                Some(user) => Some(find_user(user, conn)?.id),
+                None => None,
+            };
+
+            let scope = matches
+                .value_of("scope")
+                .and_then(|scope| scope.parse().ok())
+                .unwrap_or(models::Scope::Admin);
+
+            let (_, secret) = models::Token::create(name, user_id, scope, conn)?;
+            println!("{}", secret);
+        }
+        ("revoke", Some(matches)) => {
+            let name = matches.value_of("name").unwrap();
+
+            match models::Token::find_by_name(name, conn)? {
+                Some(token) => token.delete(conn)?,
+                None => return Err(format!("token `{}` does not exist", name).into()),
+            }
+        }
+        ("list", Some(_)) => {
+            for token in models::Token::all(conn)? {
+                let user = match token.user_id {
+                    Some(user_id) => models::User::find_by_id(user_id, conn)?.map(|user| user.name),
+                    None => None,
+                };
+
+                println!(
+                    "{}\t{}\t{}\tcreated {}\tlast used {}",
+                    token.name,
+                    user.as_deref().unwrap_or("-"),
+                    token.scope.as_str(),
+                    token.created_at,
+                    token
+                        .last_used_at
+                        .map_or_else(|| "never".to_owned(), |at| at.to_string())
+                );
+            }
+        }
+        _ => unreachable!(),
+    }
+
+    Ok(())
+}
+
+fn user(matches: &ArgMatches, conn: &Connection) -> Result<(), Box<dyn Error>> {
+    match matches.subcommand() {
+        ("create", Some(matches)) => {
+            let name = matches.value_of("name").unwrap();
+            let role = matches
+                .value_of("role")
+                .and_then(|role| role.parse().ok())
+                .unwrap_or(models::Permission::Read);
+
+            if models::User::find_by_name(name, conn)?.is_some() {
+                return Err(format!("user `{}` already exists", name).into());
+            }
+
+            models::User::create(name, role, conn)?;
+        }
+        ("role", Some(matches)) => {
+            let role = matches.value_of("role").unwrap().parse().unwrap();
+
+            find_user(matches.value_of("name").unwrap(), conn)?.set_role(role, conn)?;
+        }
+        ("delete", Some(matches)) => {
+            let user = find_user(matches.value_of("name").unwrap(), conn)?;
+            let heir = find_user(matches.value_of("transfer-to").unwrap(), conn)?;
+
+            if heir.id == user.id {
+                return Err(format!("user `{}` cannot get its own entries", user.name).into());
+            }
+
+            user.delete(&heir, conn)?;
+        }
+        ("list", Some(_)) => {
+            for user in models::User::all(conn)? {
+                println!(
+                    "{}\t{}\tcreated {}",
+                    user.name,
+                    user.role.as_str(),
+                    user.created_at
+                );
+            }
+        }
+        _ => unreachable!(),
+    }
+
+    Ok(())
+}
+
+fn sidecar(matches: &ArgMatches, conn: &mut Connection) -> Result<(), Box<dyn Error>> {
+    let (command, sub_matches) = matches.subcommand();
+    let sub_matches = sub_matches.unwrap();
+    let dir = std::path::Path::new(sub_matches.value_of("dir").unwrap());
+    // global option is seen only by the command it follows
+    let format = sub_matches
+        .value_of("format")
+        .or_else(|| matches.value_of("format"))
+        .and_then(|format| format.parse().ok())
+        .unwrap_or(tagz::sidecar::Format::Xmp);
+    let mut library = tagz::Library::new(conn, tagz::Session::local("sidecar"));
+
+    match command {
+        "import" => {
+            let tagged = tagz::sidecar::import(dir, format, &mut library)?;
+
+            info!("Tagged {} files.", tagged);
+        }
+        "export" => {
+            let written = tagz::sidecar::export(dir, format, &library)?;
+
+            info!("Wrote {} {} sidecars.", written, format.as_str());
+        }
+        _ => unreachable!(),
+    }
+
+    Ok(())
+}
+
+fn import(matches: &ArgMatches, conn: &mut Connection) -> Result<(), Box<dyn Error>> {
+    let (source, sub_matches) = matches.subcommand();
+    let sub_matches = sub_matches.unwrap();
+    // global flag is seen only by the command it follows
+    let dry_run = matches.is_present("dry-run") || sub_matches.is_present("dry-run");
+    let mut library = tagz::Library::new(conn, tagz::Session::local("import"));
+
+    let report = match source {
+        "tmsu" => {
+            let db = std::path::Path::new(sub_matches.value_of("db").unwrap());
+
+            tagz::import::tmsu(db, &mut library, dry_run)?
+        }
+        "hydrus" => {
+            let dir = std::path::Path::new(sub_matches.value_of("dir").unwrap());
+
+            tagz::import::hydrus(dir, &mut library, dry_run)?
+        }
+        _ => unreachable!(),
+    };
+
+    if dry_run {
+        println!("Dry run, nothing changed: {}.", report);
+    } else {
+        println!("Imported: {}.", report);
+    }
+
+    Ok(())
+}
+
+fn materialize(matches: &ArgMatches, conn: &mut Connection) -> Result<(), Box<dyn Error>> {
+    let dir = std::path::Path::new(matches.value_of("dir").unwrap());
+    let depth = matches
+        .value_of("depth")
+        .map_or(2, |depth| depth.parse().unwrap());
+
+    loop {
+        let changed = tagz::materialize::run(dir, depth, conn)?;
+
+        if !matches.is_present("watch") {
+            return Ok(());
+        }
+
+        if changed > 0 {
+            info!("Relinked {} files.", changed);
+        }
+
+        std::thread::sleep(std::time::Duration::from_secs(2));
+    }
+}
+
+fn find_user(name: &str, conn: &Connection) -> Result<models::User, Box<dyn Error>> {
+    models::User::find_by_name(name, conn)?
+        .ok_or_else(|| format!("user `{}` does not exist", name).into())
+}
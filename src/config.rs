@@ -5,8 +5,14 @@ lazy_static! {
     pub static ref LIST_TAGS_PER_PAGE: Arc<Mutex<u32>> = Arc::new(Mutex::new(50));
     pub static ref LIST_FILES_PER_PAGE: Arc<Mutex<u32>> = Arc::new(Mutex::new(50));
     pub static ref LIST_FILES_BY_TAG_PER_PAGE: Arc<Mutex<u32>> = Arc::new(Mutex::new(2));
+    pub static ref LIST_RELATED_TAGS_LIMIT: Arc<Mutex<u32>> = Arc::new(Mutex::new(10));
+    pub static ref LIST_AUDIT_PER_PAGE: Arc<Mutex<u32>> = Arc::new(Mutex::new(50));
+    pub static ref LIST_TRASH_PER_PAGE: Arc<Mutex<u32>> = Arc::new(Mutex::new(50));
+    /// milliseconds between checks of the change log by event streams
+    pub static ref EVENTS_POLL_INTERVAL: Arc<Mutex<u32>> = Arc::new(Mutex::new(500));
 }
 
+/// Schema of the first release, later changes are made by [`MIGRATIONS`].
 pub static TABLES: &[&str] = &[
     // `files`
     r#"
@@ -36,3 +42,231 @@ pub static TABLES: &[&str] = &[
 		)
 	"#,
 ];
+
+/// Change of the schema, columns are added to tables lacking them.
+pub struct Migration {
+    /// `(table, column definition)`
+    pub columns: &'static [(&'static str, &'static str)],
+    pub statements: &'static [&'static str],
+}
+
+/// Schema changes in order, `PRAGMA user_version` of a database is the number
+/// of applied ones. Databases made before versions were tracked may already
+/// have some of the tables, so every step only adds what is missing.
+pub static MIGRATIONS: &[Migration] = &[
+    //// search
+    Migration {
+        columns: &[("tags", "description TEXT")],
+        statements: &[
+            // `files_search`, `tagz_tokens` is registered on every connection
+            r#"
+		CREATE VIRTUAL TABLE IF NOT EXISTS `files_search` USING fts5(tokens)
+	"#,
+            r#"
+		CREATE TRIGGER IF NOT EXISTS `files_search_insert` AFTER INSERT ON `files` BEGIN
+			INSERT INTO `files_search` (rowid, tokens) VALUES (new.id, tagz_tokens(new.name));
+		END
+	"#,
+            r#"
+		CREATE TRIGGER IF NOT EXISTS `files_search_update` AFTER UPDATE OF name ON `files` BEGIN
+			UPDATE `files_search` SET tokens=tagz_tokens(new.name) WHERE rowid=new.id;
+		END
+	"#,
+            r#"
+		CREATE TRIGGER IF NOT EXISTS `files_search_delete` AFTER DELETE ON `files` BEGIN
+			DELETE FROM `files_search` WHERE rowid=old.id;
+		END
+	"#,
+            // `tags_search`
+            r#"
+		CREATE VIRTUAL TABLE IF NOT EXISTS `tags_search` USING fts5(name, description)
+	"#,
+            r#"
+		CREATE TRIGGER IF NOT EXISTS `tags_search_insert` AFTER INSERT ON `tags` BEGIN
+			INSERT INTO `tags_search` (rowid, name, description) VALUES (new.id, tagz_tokens(new.name), tagz_tokens(new.description));
+		END
+	"#,
+            r#"
+		CREATE TRIGGER IF NOT EXISTS `tags_search_update` AFTER UPDATE OF name, description ON `tags` BEGIN
+			UPDATE `tags_search` SET name=tagz_tokens(new.name), description=tagz_tokens(new.description) WHERE rowid=new.id;
+		END
+	"#,
+            r#"
+		CREATE TRIGGER IF NOT EXISTS `tags_search_delete` AFTER DELETE ON `tags` BEGIN
+			DELETE FROM `tags_search` WHERE rowid=old.id;
+		END
+	"#,
+            // rows made before the index
+            r#"
+		INSERT INTO `files_search` (rowid, tokens)
+		SELECT id, tagz_tokens(name) FROM `files` WHERE id NOT IN (SELECT rowid FROM `files_search`)
+	"#,
+            r#"
+		INSERT INTO `tags_search` (rowid, name, description)
+		SELECT id, tagz_tokens(name), tagz_tokens(description) FROM `tags` WHERE id NOT IN (SELECT rowid FROM `tags_search`)
+	"#,
+        ],
+    },
+    //// saved searches
+    Migration {
+        columns: &[],
+        statements: &[r#"
+		CREATE TABLE IF NOT EXISTS `searches` (
+			id INTEGER PRIMARY KEY AUTOINCREMENT,
+			name VACHAR(256) UNIQUE NOT NULL,
+			tags TEXT NOT NULL DEFAULT(''),
+			query TEXT,
+			sort VACHAR(16) NOT NULL DEFAULT('newest'),
+			exact BOOLEAN NOT NULL DEFAULT(0),
+			updated_at TIMESTAMP NOT NULL DEFAULT(CURRENT_TIMESTAMP),
+			created_at TIMESTAMP NOT NULL DEFAULT(CURRENT_TIMESTAMP)
+		)
+	"#],
+    },
+    //// collections
+    Migration {
+        columns: &[],
+        statements: &[
+            r#"
+		CREATE TABLE IF NOT EXISTS `collections` (
+			id INTEGER PRIMARY KEY AUTOINCREMENT,
+			name VACHAR(256) UNIQUE NOT NULL,
+			updated_at TIMESTAMP NOT NULL DEFAULT(CURRENT_TIMESTAMP),
+			created_at TIMESTAMP NOT NULL DEFAULT(CURRENT_TIMESTAMP)
+		)
+	"#,
+            r#"
+		CREATE TABLE IF NOT EXISTS `collection_files` (
+			collection_id INTEGER NOT NULL,
+			file_id INTEGER NOT NULL,
+			position INTEGER NOT NULL,
+
+		    PRIMARY KEY (collection_id, file_id)
+		)
+	"#,
+            r#"
+		CREATE TRIGGER IF NOT EXISTS `collection_files_compact` AFTER DELETE ON `collection_files` BEGIN
+			UPDATE `collection_files` SET position=position - 1 WHERE collection_id=old.collection_id AND position>old.position;
+		END
+	"#,
+            r#"
+		CREATE TRIGGER IF NOT EXISTS `files_collections_delete` AFTER DELETE ON `files` BEGIN
+			DELETE FROM `collection_files` WHERE file_id=old.id;
+		END
+	"#,
+        ],
+    },
+    //// tokens
+    Migration {
+        columns: &[],
+        statements: &[r#"
+		CREATE TABLE IF NOT EXISTS `tokens` (
+			id INTEGER PRIMARY KEY AUTOINCREMENT,
+			name VACHAR(256) UNIQUE NOT NULL,
+			hash VACHAR(64) UNIQUE NOT NULL,
+			last_used_at TIMESTAMP,
+			created_at TIMESTAMP NOT NULL DEFAULT(CURRENT_TIMESTAMP)
+		)
+	"#],
+    },
+    //// users and ownership
+    Migration {
+        columns: &[
+            ("files", "owner_id INTEGER"),
+            ("files", "shared BOOLEAN NOT NULL DEFAULT(1)"),
+            ("tags", "owner_id INTEGER"),
+            ("tags", "shared BOOLEAN NOT NULL DEFAULT(1)"),
+            ("tokens", "user_id INTEGER"),
+        ],
+        statements: &[r#"
+		CREATE TABLE IF NOT EXISTS `users` (
+			id INTEGER PRIMARY KEY AUTOINCREMENT,
+			name VACHAR(256) UNIQUE NOT NULL,
+			role VACHAR(8) NOT NULL DEFAULT('read'),
+			created_at TIMESTAMP NOT NULL DEFAULT(CURRENT_TIMESTAMP)
+		)
+	"#],
+    },
+    //// token scopes
+    Migration {
+        columns: &[("tokens", "scope VACHAR(8) NOT NULL DEFAULT('admin')")],
+        statements: &[],
+    },
+    //// audit, append-only
+    Migration {
+        columns: &[],
+        statements: &[
+            r#"
+		CREATE TABLE IF NOT EXISTS `audit` (
+			id INTEGER PRIMARY KEY AUTOINCREMENT,
+			actor VACHAR(256) NOT NULL,
+			action VACHAR(32) NOT NULL,
+			before TEXT,
+			after TEXT,
+			created_at TIMESTAMP NOT NULL DEFAULT(CURRENT_TIMESTAMP)
+		)
+	"#,
+            r#"
+		CREATE TRIGGER IF NOT EXISTS `audit_no_update` BEFORE UPDATE ON `audit` BEGIN
+			SELECT RAISE(ABORT, 'audit is append-only');
+		END
+	"#,
+            r#"
+		CREATE TRIGGER IF NOT EXISTS `audit_no_delete` BEFORE DELETE ON `audit` BEGIN
+			SELECT RAISE(ABORT, 'audit is append-only');
+		END
+	"#,
+        ],
+    },
+    //// trash, deleted files and tags with their relations
+    Migration {
+        columns: &[],
+        statements: &[r#"
+		CREATE TABLE IF NOT EXISTS `trash` (
+			id INTEGER PRIMARY KEY AUTOINCREMENT,
+			kind VACHAR(8) NOT NULL,
+			name VACHAR(4096) NOT NULL,
+			owner_id INTEGER,
+			shared BOOLEAN NOT NULL DEFAULT(1),
+			entry TEXT NOT NULL,
+			relations TEXT NOT NULL,
+			actor VACHAR(256) NOT NULL,
+			deleted_at TIMESTAMP NOT NULL DEFAULT(CURRENT_TIMESTAMP)
+		)
+	"#],
+    },
+    //// webhooks
+    Migration {
+        columns: &[],
+        statements: &[r#"
+		CREATE TABLE IF NOT EXISTS `webhooks` (
+			id INTEGER PRIMARY KEY AUTOINCREMENT,
+			name VACHAR(256) UNIQUE NOT NULL,
+			url VACHAR(4096) NOT NULL,
+			secret VACHAR(256) NOT NULL,
+			events VACHAR(1024) NOT NULL DEFAULT(''),
+			tags VACHAR(4096) NOT NULL DEFAULT(''),
+			cursor INTEGER NOT NULL DEFAULT(0),
+			attempts INTEGER NOT NULL DEFAULT(0),
+			next_attempt_at TIMESTAMP,
+			last_error TEXT,
+			created_at TIMESTAMP NOT NULL DEFAULT(CURRENT_TIMESTAMP)
+		)
+	"#],
+    },
+    //// ownership of collections and saved searches
+    Migration {
+        columns: &[
+            ("collections", "owner_id INTEGER"),
+            ("collections", "shared BOOLEAN NOT NULL DEFAULT(1)"),
+            ("searches", "owner_id INTEGER"),
+            ("searches", "shared BOOLEAN NOT NULL DEFAULT(1)"),
+        ],
+        statements: &[],
+    },
+    //// places of trashed files in collections are kept in trash
+    Migration {
+        columns: &[],
+        statements: &["DROP TRIGGER IF EXISTS `files_collections_delete`"],
+    },
+];
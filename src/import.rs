@@ -0,0 +1,313 @@
+//! Migrating tags from other tools: TMSU databases and tag text files of
+//! Hydrus exports. Files are named by their paths, missing files and tags are
+//! created and existing ones get the tags they lack. An import changes the
+//! library all at once or, when it fails, not at all.
+
+use crate::{
+    library::{self, Library},
+    Connection, SqlError,
+};
+use rusqlite::params;
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::{Display, Formatter, Result as FmtResult},
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    Library(library::Error),
+    /// TMSU database cannot be read
+    Tmsu(SqlError),
+}
+
+impl Display for Error {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        match self {
+            Error::Library(err) => Display::fmt(err, fmt),
+            Error::Tmsu(err) => write!(fmt, "cannot read TMSU database: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<library::Error> for Error {
+    fn from(err: library::Error) -> Self {
+        Error::Library(err)
+    }
+}
+
+/// What importing did, or would do on a dry run.
+#[derive(Default, Debug)]
+pub struct Report {
+    pub files_created: usize,
+    /// existing files which got new tags
+    pub files_tagged: usize,
+    pub tags_created: usize,
+    pub relations_created: usize,
+}
+
+impl Report {
+    /// Files which got new tags, created ones included.
+    pub fn files(&self) -> usize {
+        self.files_created + self.files_tagged
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        write!(
+            fmt,
+            "{} files created, {} files tagged, {} tags created, {} relations created",
+            self.files_created, self.files_tagged, self.tags_created, self.relations_created
+        )
+    }
+}
+
+/// Gives files tags creating missing files and tags, changes nothing on a dry
+/// run but still fills in the report.
+pub struct Importer<'l, 'c> {
+    library: &'l mut Library<'c>,
+    dry_run: bool,
+    /// tags existing or created, including ones only pretended to be on a dry run
+    known: HashSet<String>,
+    pub report: Report,
+}
+
+impl<'l, 'c> Importer<'l, 'c> {
+    pub fn new(library: &'l mut Library<'c>, dry_run: bool) -> Self {
+        Self {
+            library,
+            dry_run,
+            known: HashSet::new(),
+            report: Report::default(),
+        }
+    }
+
+    /// Repeated tags are imported once.
+    pub fn import(&mut self, name: &str, tags: &[String]) -> library::Result<()> {
+        let mut unique = Vec::with_capacity(tags.len());
+
+        for tag in tags {
+            if !unique.contains(tag) {
+                unique.push(tag.to_owned());
+            }
+        }
+
+        let tags = unique;
+
+        if tags.is_empty() {
+            return Ok(());
+        }
+
+        for tag in &tags {
+            if self.known.contains(tag) {
+                continue;
+            }
+
+            match self.library.tag(tag) {
+                Ok(_) => {}
+                Err(library::Error::TagNotFound) => {
+                    if !self.dry_run {
+                        self.library.create_tag(tag, None, true)?;
+                    }
+
+                    self.report.tags_created += 1;
+                }
+                Err(err) => return Err(err),
+            }
+
+            self.known.insert(tag.to_owned());
+        }
+
+        match self.library.file(name) {
+            Ok(file) => {
+                let missing = tags
+                    .iter()
+                    .filter(|tag| !file.tags.iter().any(|own| &&own.name == tag))
+                    .collect::<Vec<_>>();
+
+                if missing.is_empty() {
+                    return Ok(());
+                }
+
+                if !self.dry_run {
+                    for tag in &missing {
+                        self.library.add_tag(file.id, tag)?;
+                    }
+                }
+
+                self.report.files_tagged += 1;
+                self.report.relations_created += missing.len();
+            }
+            Err(library::Error::FileNotFound) => {
+                if !self.dry_run {
+                    self.library.create_file(name, &tags, true)?;
+                }
+
+                self.report.files_created += 1;
+                self.report.relations_created += tags.len();
+            }
+            Err(err) => return Err(err),
+        }
+
+        Ok(())
+    }
+}
+
+/// Imports a TMSU database, usually `.tmsu/db`. Tags having a value become
+/// `tag=value` as TMSU writes them, implications are not applied.
+pub fn tmsu(db: &Path, library: &mut Library, dry_run: bool) -> Result<Report> {
+    let files = tmsu_files(db).map_err(Error::Tmsu)?;
+
+    Ok(library.atomically(|library| {
+        let mut importer = Importer::new(library, dry_run);
+
+        for (name, tags) in &files {
+            importer.import(name, tags)?;
+        }
+
+        Ok(importer.report)
+    })?)
+}
+
+/// TMSU keeps directories under its root, the parent of `.tmsu`, relative to it.
+fn tmsu_files(db: &Path) -> std::result::Result<BTreeMap<String, Vec<String>>, SqlError> {
+    let conn = Connection::open_with_flags(db, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let db = fs::canonicalize(db).unwrap_or_else(|_| db.to_owned());
+    let root = db
+        .parent()
+        .and_then(Path::parent)
+        .unwrap_or_else(|| Path::new(""));
+    let rows = conn
+        .prepare(
+            "SELECT f.directory, f.name, t.name, v.name FROM file_tag ft
+                JOIN file f ON f.id=ft.file_id
+                JOIN tag t ON t.id=ft.tag_id
+                LEFT JOIN value v ON v.id=ft.value_id",
+        )?
+        .query_map(params! {}, |row| {
+            Ok((
+                root.join(row.get::<_, String>(0)?)
+                    .join(row.get::<_, String>(1)?)
+                    .components()
+                    .collect::<PathBuf>(),
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<String>>(3)?,
+            ))
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    let mut files = BTreeMap::<_, Vec<_>>::new();
+
+    for (path, tag, value) in rows {
+        files
+            .entry(path.to_string_lossy().into_owned())
+            .or_default()
+            .push(match value {
+                Some(value) => format!("{}={}", tag, value),
+                None => tag,
+            });
+    }
+
+    Ok(files)
+}
+
+/// Imports tags of files under `dir` exported by Hydrus along with tag files,
+/// `image.jpg.txt` holds tags of `image.jpg` one per line, namespaced tags
+/// are kept as `namespace:tag`. Unreadable tag files are skipped.
+pub fn hydrus(dir: &Path, library: &mut Library, dry_run: bool) -> Result<Report> {
+    Ok(library.atomically(|library| hydrus_files(dir, library, dry_run))?)
+}
+
+fn hydrus_files(dir: &Path, library: &mut Library, dry_run: bool) -> library::Result<Report> {
+    let mut importer = Importer::new(library, dry_run);
+
+    crate::scan::walk(dir, |path| {
+        let file = match path.to_str() {
+            Some(name) if name.ends_with(".txt") => Path::new(&name[..name.len() - 4]),
+            _ => return Ok(()),
+        };
+
+        // text files which are not tags of another file
+        if !file.is_file() {
+            return Ok(());
+        }
+
+        match fs::read_to_string(path) {
+            Ok(content) => {
+                let tags = content
+                    .lines()
+                    .map(str::trim)
+                    .filter(|tag| !tag.is_empty())
+                    .map(ToOwned::to_owned)
+                    .collect::<Vec<_>>();
+
+                importer.import(file.to_str().unwrap(), &tags)
+            }
+            Err(err) => {
+                log::warn!("Cannot read tags of {}: {}", file.display(), err);
+
+                Ok(())
+            }
+        }
+    })?;
+
+    Ok(importer.report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Session;
+
+    #[test]
+    fn resolves_tmsu_directories_against_its_root() {
+        let root = std::env::temp_dir().join(format!("tagz-tmsu-{}", std::process::id()));
+        fs::remove_dir_all(&root).ok();
+        fs::create_dir_all(root.join(".tmsu")).unwrap();
+
+        let db = root.join(".tmsu/db");
+        Connection::open(&db)
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE file (id INTEGER PRIMARY KEY, directory TEXT, name TEXT);
+                 CREATE TABLE tag (id INTEGER PRIMARY KEY, name TEXT);
+                 CREATE TABLE value (id INTEGER PRIMARY KEY, name TEXT);
+                 CREATE TABLE file_tag (file_id INTEGER, tag_id INTEGER, value_id INTEGER);
+                 INSERT INTO file VALUES (1, '.', 'a.jpg'), (2, 'sub', 'b.jpg'), (3, '/photos', 'c.jpg');
+                 INSERT INTO tag VALUES (1, 'cat'), (2, 'year');
+                 INSERT INTO value VALUES (1, '2020');
+                 INSERT INTO file_tag VALUES (1, 1, 0), (2, 2, 1), (3, 1, 0), (3, 2, 1);",
+            )
+            .unwrap();
+
+        let root = fs::canonicalize(&root).unwrap();
+        let mut conn = crate::get_conn(Path::new(":memory:")).unwrap();
+        let mut library = Library::new(&mut conn, Session::local("test"));
+        let report = tmsu(&db, &mut library, false).unwrap();
+
+        assert_eq!(report.files_created, 3);
+        assert_eq!(report.relations_created, 4);
+
+        let tags = |name: &Path| {
+            library
+                .file(name.to_str().unwrap())
+                .unwrap()
+                .tags
+                .into_iter()
+                .map(|tag| tag.name)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(tags(&root.join("a.jpg")), ["cat"]);
+        assert_eq!(tags(&root.join("sub/b.jpg")), ["year=2020"]);
+        assert_eq!(tags(Path::new("/photos/c.jpg")).len(), 2);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -5,27 +5,37 @@ extern crate lazy_static;
 
 pub use app_config::AppConfig;
 pub use from_row::FromRow;
+pub use library::{Library, Session};
 pub use rusqlite::{Connection, Error as SqlError, Result as SqlResult};
 use std::path::Path;
 
 mod app_config;
 mod config;
 mod from_row;
+pub mod import;
+pub mod library;
+pub mod materialize;
 pub mod models;
+mod scan;
 pub mod serv;
+pub mod sidecar;
+pub mod xattr;
 
 #[inline]
 pub fn get_conn(path: &Path) -> SqlResult<Connection> {
     // check if file exists
     let exists = path.exists();
 
-    let connection = Connection::open(path)?;
+    let mut connection = Connection::open(path)?;
     rusqlite::vtab::array::load_module(&connection).unwrap();
+    models::search::register_functions(&connection)?;
 
     if !exists {
         prepare_tables(&connection)?;
     }
 
+    migrate(&mut connection)?;
+
     Ok(connection)
 }
 
@@ -37,3 +47,41 @@ pub fn prepare_tables(conn: &Connection) -> SqlResult<()> {
 
     Ok(())
 }
+
+/// Applies migrations the database lacks, each along with its version.
+pub fn migrate(conn: &mut Connection) -> SqlResult<()> {
+    let version: i64 = conn.query_row("PRAGMA user_version", rusqlite::params! {}, |row| {
+        row.get(0)
+    })?;
+
+    for (index, migration) in config::MIGRATIONS.iter().enumerate().skip(version as usize) {
+        let tx = conn.transaction()?;
+
+        for (table, column) in migration.columns {
+            let name = column.split_whitespace().next().unwrap();
+            let exists: bool = tx.query_row(
+                "SELECT COUNT(*) > 0 FROM pragma_table_info(?1) WHERE name=?2",
+                rusqlite::params! {table, name},
+                |row| row.get(0),
+            )?;
+
+            if !exists {
+                tx.execute(
+                    &format!("ALTER TABLE `{}` ADD COLUMN {}", table, column),
+                    rusqlite::params! {},
+                )?;
+            }
+        }
+
+        for statement in migration.statements {
+            tx.execute_batch(statement)?;
+        }
+
+        tx.execute_batch(&format!("PRAGMA user_version = {}", index + 1))?;
+        tx.commit()?;
+
+        log::info!("Database migrated to version {}.", index + 1);
+    }
+
+    Ok(())
+}
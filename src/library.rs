@@ -0,0 +1,1180 @@
+//! Operations on files, tags and everything built on them usable without the
+//! HTTP server.
+//!
+//! [`Library`] borrows a connection opened with [`crate::get_conn`] and acts on
+//! behalf of a [`Session`]: it hides entries the session cannot see, refuses to
+//! change entries owned by others and records every change in the audit log.
+//! Routes of `api/v1` are adapters translating [`Error`] into service errors.
+
+use crate::{
+    models::{
+        self, audit, search, stats, Access, Collection, File, SavedSearch, Tag, Trash, Webhook,
+    },
+    Connection, SqlError,
+};
+use std::{
+    collections::HashSet,
+    fmt::{Display, Formatter, Result as FmtResult},
+};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    FileNotFound,
+    TagNotFound,
+    /// names of unknown or invisible tags
+    TagsNotFound(Vec<String>),
+    FileDuplication,
+    TagDuplication,
+    RelationNotFound,
+    RelationExists,
+    /// tag still has files, see [`Library::delete_tag`]
+    ConfirmationRequired,
+    PermissionDenied,
+    /// name is held by an entry invisible to the session, which is not told apart
+    NameTaken,
+    CollectionNotFound,
+    CollectionDuplication,
+    CollectionFileNotFound,
+    CollectionFileExists,
+    /// new order does not list every file of the collection exactly once
+    CollectionOrderMismatch,
+    SearchNotFound,
+    SearchDuplication,
+    TrashNotFound,
+    WebhookNotFound,
+    WebhookDuplication,
+    /// url is not `http` nor `https`
+    WebhookInvalidUrl,
+    Sql(SqlError),
+}
+
+impl Display for Error {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        match self {
+            Error::FileNotFound => write!(fmt, "file cannot be found"),
+            Error::TagNotFound => write!(fmt, "tag cannot be found"),
+            Error::TagsNotFound(names) => write!(fmt, "unknown tags: {}", names.join(", ")),
+            Error::FileDuplication => write!(fmt, "file with the given name already exists"),
+            Error::TagDuplication => write!(fmt, "tag with the given name already exists"),
+            Error::RelationNotFound => write!(fmt, "file does not have the tag"),
+            Error::RelationExists => write!(fmt, "file already has the tag"),
+            Error::ConfirmationRequired => write!(fmt, "tag has files, deletion must be confirmed"),
+            Error::PermissionDenied => write!(fmt, "entry is owned by another user"),
+            Error::NameTaken => write!(fmt, "name is already taken"),
+            Error::CollectionNotFound => write!(fmt, "collection cannot be found"),
+            Error::CollectionDuplication => {
+                write!(fmt, "collection with the given name already exists")
+            }
+            Error::CollectionFileNotFound => write!(fmt, "collection does not contain the file"),
+            Error::CollectionFileExists => write!(fmt, "collection already contains the file"),
+            Error::CollectionOrderMismatch => {
+                write!(fmt, "order must list every file of the collection once")
+            }
+            Error::SearchNotFound => write!(fmt, "saved search cannot be found"),
+            Error::SearchDuplication => {
+                write!(fmt, "saved search with the given name already exists")
+            }
+            Error::TrashNotFound => write!(fmt, "trash entry cannot be found"),
+            Error::WebhookNotFound => write!(fmt, "webhook cannot be found"),
+            Error::WebhookDuplication => write!(fmt, "webhook with the given name already exists"),
+            Error::WebhookInvalidUrl => write!(fmt, "webhook url must be http or https"),
+            Error::Sql(err) => Display::fmt(err, fmt),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Sql(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<SqlError> for Error {
+    fn from(err: SqlError) -> Self {
+        Error::Sql(err)
+    }
+}
+
+/// On whose behalf the library works.
+#[derive(Clone, Debug)]
+pub struct Session {
+    pub user_id: Option<i32>,
+    pub access: Access,
+    /// may change entries owned by other users
+    pub admin: bool,
+    /// name recorded in audit
+    pub actor: String,
+}
+
+impl Session {
+    /// Sees and changes everything, for applications owning the database.
+    pub fn local<A>(actor: A) -> Self
+    where
+        A: Into<String>,
+    {
+        Self {
+            user_id: None,
+            access: Access::All,
+            admin: true,
+            actor: actor.into(),
+        }
+    }
+}
+
+/// Files filter, tags are given by names.
+#[derive(Default)]
+pub struct Query<'a> {
+    pub tags: &'a [&'a str],
+    /// files must have every tag instead of any
+    pub exact: bool,
+    /// words searched in names and tags, see [`search::match_expression`]
+    pub text: Option<&'a str>,
+    pub sort: search::Sort,
+}
+
+/// Webhook settings, see [`models::Webhook`].
+pub struct Hook<'a> {
+    pub name: &'a str,
+    pub url: &'a str,
+    /// generated on creation when absent, kept on update
+    pub secret: Option<&'a str>,
+    /// empty for all
+    pub events: &'a [audit::Action],
+    /// empty for any
+    pub tags: &'a [&'a str],
+}
+
+impl Hook<'_> {
+    fn events(&self) -> String {
+        self.events
+            .iter()
+            .map(|action| action.as_str())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+pub struct Library<'c> {
+    conn: &'c mut Connection,
+    session: Session,
+    xattr: bool,
+}
+
+impl<'c> Library<'c> {
+    pub fn new(conn: &'c mut Connection, session: Session) -> Self {
+        Self {
+            conn,
+            session,
+            xattr: false,
+        }
+    }
+
+    /// Writes tags of changed files to their extended attribute, see [`crate::xattr`].
+    pub fn with_xattr(mut self, xattr: bool) -> Self {
+        self.xattr = xattr;
+        self
+    }
+
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    /// Runs `f` in a savepoint, so its changes are kept all together or, when
+    /// it fails, not at all. Nests like [`models::atomically`].
+    pub fn atomically<T, F>(&mut self, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Self) -> Result<T>,
+    {
+        self.conn.execute_batch("SAVEPOINT `library`")?;
+
+        match f(self) {
+            Ok(value) => {
+                self.conn.execute_batch("RELEASE `library`")?;
+
+                Ok(value)
+            }
+            Err(err) => {
+                self.conn
+                    .execute_batch("ROLLBACK TO `library`; RELEASE `library`")?;
+
+                Err(err)
+            }
+        }
+    }
+
+    //---
+    pub fn tags(&self) -> Result<Vec<Tag>> {
+        Ok(Tag::all(self.session.access, self.conn)?)
+    }
+
+    /// Private tags of other users are reported as missing.
+    pub fn tag(&self, name: &str) -> Result<Tag> {
+        match Tag::find_by_name(name, self.conn)? {
+            Some(tag) if self.session.access.can_see(tag.owner_id, tag.shared) => Ok(tag),
+            _ => Err(Error::TagNotFound),
+        }
+    }
+
+    pub fn tags_by_names<S>(&self, names: &[S]) -> Result<Vec<Tag>>
+    where
+        S: AsRef<str> + ToString,
+    {
+        let tags = Tag::find_all_where_in_names(names, self.session.access, self.conn)?;
+        let mut unique = names.iter().map(AsRef::as_ref).collect::<HashSet<_>>();
+
+        if tags.len() != unique.len() {
+            for tag in &tags {
+                unique.remove(tag.name.as_str());
+            }
+
+            Err(Error::TagsNotFound(
+                names
+                    .iter()
+                    .filter(|name| unique.remove(name.as_ref()))
+                    .map(ToString::to_string)
+                    .collect(),
+            ))
+        } else {
+            Ok(tags)
+        }
+    }
+
+    pub fn create_tag(&self, name: &str, description: Option<&str>, shared: bool) -> Result<Tag> {
+        self.require_free_tag_name(name)?;
+
+        Ok(Tag::create_with_description(
+            name,
+            description,
+            self.session.user_id,
+            shared,
+            &self.session.actor,
+            self.conn,
+        )?)
+    }
+
+    pub fn rename_tag(&self, name: &str, new_name: &str) -> Result<Tag> {
+        let mut tag = self.tag(name)?;
+
+        self.require_owner(tag.owner_id)?;
+
+        if tag.name != new_name {
+            self.require_free_tag_name(new_name)?;
+        }
+
+        tag.rename(new_name, &self.session.actor, self.conn)?;
+        self.store_xattr_of_tag(tag.id)?;
+
+        Ok(tag)
+    }
+
+    /// Moves tag to trash, a tag having files is unlinked from them only if `confirm`.
+    pub fn delete_tag(&mut self, name: &str, confirm: bool) -> Result<()> {
+        let tag = self.tag(name)?;
+
+        self.require_owner(tag.owner_id)?;
+
+        if !confirm && tag.has_related_files(self.conn)? {
+            return Err(Error::ConfirmationRequired);
+        }
+
+        let files = self.files_ids_of_tag(tag.id)?;
+
+        models::Trash::put_tag(&tag, &self.session.actor, self.conn)?;
+
+        for file_id in files {
+            self.store_xattr(file_id)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn search_tags(&self, text: &str) -> Result<Vec<Tag>> {
+        match search::match_expression(text) {
+            Some(text) => Ok(Tag::search(&text, self.session.access, self.conn)?),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    pub fn related_tags<S>(&self, names: &[S], limit: u32) -> Result<Vec<stats::RelatedTag>>
+    where
+        S: AsRef<str> + ToString,
+    {
+        let ids = self.tags_ids(names)?;
+
+        Ok(stats::RelatedTag::find_for_tags_ids(
+            &ids,
+            limit,
+            self.session.access,
+            self.conn,
+        )?)
+    }
+
+    //---
+    /// Not shared files of other users are reported as missing.
+    pub fn file(&self, name: &str) -> Result<File> {
+        self.visible_file(File::find_by_name(name, self.conn)?)
+    }
+
+    pub fn file_by_id(&self, id: i32) -> Result<File> {
+        self.visible_file(File::find_by_id(id, self.conn)?)
+    }
+
+    pub fn create_file<S>(&mut self, name: &str, tags: &[S], shared: bool) -> Result<File>
+    where
+        S: AsRef<str> + ToString,
+    {
+        self.require_free_file_name(name)?;
+
+        let tags = self.tags_by_names(tags)?;
+        let mut file = File::create_with_tags(
+            name,
+            &tags.iter().map(|tag| tag.id).collect::<Box<[_]>>(),
+            self.session.user_id,
+            shared,
+            &self.session.actor,
+            self.conn,
+        )?;
+
+        file.tags = tags;
+        self.store_xattr(file.id)?;
+
+        Ok(file)
+    }
+
+    /// Moves file to trash along with its relations.
+    pub fn delete_file(&mut self, name: &str) -> Result<()> {
+        let mut file = self.file(name)?;
+
+        self.require_owner(file.owner_id)?;
+
+        file.update_tags(self.conn)?;
+
+        models::Trash::put_file(&file, &self.session.actor, self.conn)?;
+
+        if self.xattr {
+            if let Err(err) = crate::xattr::clear(&file.name) {
+                log::warn!("Cannot clear tags of {}: {}", file.name, err);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Only the owner of the file or an admin may change its tags.
+    pub fn add_tag(&self, file_id: i32, tag: &str) -> Result<()> {
+        self.require_owner(self.file_by_id(file_id)?.owner_id)?;
+
+        let tag = self.tag(tag)?;
+
+        if models::relationships::file_id_and_tag_id_exists(file_id, tag.id, self.conn)? {
+            return Err(Error::RelationExists);
+        }
+
+        models::relationships::FileTag::create(file_id, tag.id, &self.session.actor, self.conn)?;
+
+        self.store_xattr(file_id)
+    }
+
+    /// Only the owner of the file or an admin may change its tags.
+    pub fn remove_tag(&self, file_id: i32, tag: &str) -> Result<()> {
+        let tag = self.tag(tag)?;
+
+        self.require_owner(self.file_by_id(file_id)?.owner_id)?;
+
+        if models::relationships::delete_between_file_id_and_tag_id(
+            file_id,
+            tag.id,
+            &self.session.actor,
+            self.conn,
+        )? {
+            self.store_xattr(file_id)
+        } else {
+            Err(Error::RelationNotFound)
+        }
+    }
+
+    pub fn related_to_file(&self, file_id: i32, limit: u32) -> Result<Vec<stats::RelatedTag>> {
+        self.file_by_id(file_id)?;
+
+        Ok(stats::RelatedTag::find_for_file_id(
+            file_id,
+            limit,
+            self.session.access,
+            self.conn,
+        )?)
+    }
+
+    pub fn files(&self, query: &Query, amount: u32, page: u32) -> Result<Vec<File>> {
+        let ids = self.tags_ids(query.tags)?;
+
+        Ok(File::find_specific_amount_by_query_on_page(
+            &self.file_query(query, &ids),
+            amount,
+            page,
+            self.conn,
+        )?)
+    }
+
+    /// Number of files matching `query` along with tags of them.
+    pub fn facets(&self, query: &Query) -> Result<(i64, Vec<stats::Facet>)> {
+        let ids = self.tags_ids(query.tags)?;
+        let query = self.file_query(query, &ids);
+
+        Ok((
+            stats::count_files_by_query(&query, self.conn)?,
+            stats::Facet::find_for_query(&query, self.conn)?,
+        ))
+    }
+
+    //---
+    pub fn collections(&self) -> Result<Vec<Collection>> {
+        Ok(Collection::all(self.session.access, self.conn)?)
+    }
+
+    /// Files are loaded in collection order, invisible ones are left out.
+    pub fn collection(&self, name: &str) -> Result<Collection> {
+        let mut collection = self.visible_collection(name)?;
+        collection.update_files(self.session.access, self.conn)?;
+
+        Ok(collection)
+    }
+
+    pub fn create_collection(
+        &mut self,
+        name: &str,
+        files: &[i32],
+        shared: bool,
+    ) -> Result<Collection> {
+        self.require_free(
+            Collection::find_by_name(name, self.conn)?.map(|c| (c.owner_id, c.shared)),
+            Error::CollectionDuplication,
+        )?;
+
+        let mut unique = HashSet::new();
+
+        for file in files {
+            if !unique.insert(*file) {
+                return Err(Error::CollectionFileExists);
+            }
+
+            self.file_by_id(*file)?;
+        }
+
+        let mut collection =
+            Collection::create_with_files(name, files, self.session.user_id, shared, self.conn)?;
+        collection.update_files(self.session.access, self.conn)?;
+
+        Ok(collection)
+    }
+
+    pub fn rename_collection(&self, name: &str, new_name: &str) -> Result<()> {
+        let mut collection = self.owned_collection(name)?;
+
+        if collection.name != new_name {
+            self.require_free(
+                Collection::find_by_name(new_name, self.conn)?.map(|c| (c.owner_id, c.shared)),
+                Error::CollectionDuplication,
+            )?;
+
+            collection.rename(new_name, self.conn)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn delete_collection(&self, name: &str) -> Result<()> {
+        Ok(self.owned_collection(name)?.delete(self.conn)?)
+    }
+
+    /// Returns position the file is inserted at, see [`Collection::insert_file`].
+    pub fn add_to_collection(
+        &mut self,
+        name: &str,
+        file_id: i32,
+        position: Option<u32>,
+    ) -> Result<u32> {
+        let collection = self.owned_collection(name)?;
+
+        if collection.position_of(file_id, self.conn)?.is_some() {
+            return Err(Error::CollectionFileExists);
+        }
+
+        self.file_by_id(file_id)?;
+
+        Ok(collection.insert_file(file_id, position, self.conn)?)
+    }
+
+    pub fn remove_from_collection(&self, name: &str, file_id: i32) -> Result<()> {
+        let collection = self.owned_collection(name)?;
+
+        if collection.position_of(file_id, self.conn)?.is_none() {
+            return Err(Error::CollectionFileNotFound);
+        }
+
+        Ok(collection.remove_file(file_id, self.conn)?)
+    }
+
+    /// Returns position the file is moved to, see [`Collection::move_file`].
+    pub fn move_in_collection(&mut self, name: &str, file_id: i32, position: u32) -> Result<u32> {
+        let collection = self.owned_collection(name)?;
+
+        match collection.position_of(file_id, self.conn)? {
+            Some(from) => Ok(collection.move_file(file_id, from, position, self.conn)?),
+            None => Err(Error::CollectionFileNotFound),
+        }
+    }
+
+    /// `files` must list every file of the collection exactly once.
+    pub fn reorder_collection(&mut self, name: &str, files: &[i32]) -> Result<()> {
+        let collection = self.owned_collection(name)?;
+
+        let mut current = collection.files_ids(self.conn)?;
+        let mut requested = files.to_vec();
+        current.sort_unstable();
+        requested.sort_unstable();
+
+        if current != requested {
+            return Err(Error::CollectionOrderMismatch);
+        }
+
+        Ok(collection.reorder(files, self.conn)?)
+    }
+
+    //---
+    pub fn searches(&self) -> Result<Vec<SavedSearch>> {
+        let mut visible = Vec::new();
+
+        for search in SavedSearch::all(self.session.access, self.conn)? {
+            if self.sees_tags_of(&search)? {
+                visible.push(search);
+            }
+        }
+
+        Ok(visible)
+    }
+
+    /// Searches naming a private tag of another user are reported as missing.
+    pub fn search(&self, name: &str) -> Result<SavedSearch> {
+        match SavedSearch::find_by_name(name, self.conn)? {
+            Some(search)
+                if self.session.access.can_see(search.owner_id, search.shared)
+                    && self.sees_tags_of(&search)? =>
+            {
+                Ok(search)
+            }
+            _ => Err(Error::SearchNotFound),
+        }
+    }
+
+    /// Tags of `query` must be visible to the session.
+    pub fn create_search(&self, name: &str, query: &Query, shared: bool) -> Result<SavedSearch> {
+        self.require_free(
+            SavedSearch::find_by_name(name, self.conn)?.map(|s| (s.owner_id, s.shared)),
+            Error::SearchDuplication,
+        )?;
+        self.tags_by_names(query.tags)?;
+
+        Ok(SavedSearch::create(
+            name,
+            query.tags.join(","),
+            query.text,
+            query.sort,
+            query.exact,
+            self.session.user_id,
+            shared,
+            self.conn,
+        )?)
+    }
+
+    /// Sharing is kept when `shared` is `None`.
+    pub fn update_search(
+        &self,
+        name: &str,
+        new_name: &str,
+        query: &Query,
+        shared: Option<bool>,
+    ) -> Result<SavedSearch> {
+        let mut search = self.search(name)?;
+
+        self.require_owner(search.owner_id)?;
+
+        if search.name != new_name {
+            self.require_free(
+                SavedSearch::find_by_name(new_name, self.conn)?.map(|s| (s.owner_id, s.shared)),
+                Error::SearchDuplication,
+            )?;
+        }
+
+        self.tags_by_names(query.tags)?;
+
+        search.name = new_name.to_owned();
+        search.tags = query.tags.join(",");
+        search.query = query.text.map(ToOwned::to_owned);
+        search.sort = query.sort;
+        search.exact = query.exact;
+        search.shared = shared.unwrap_or(search.shared);
+        search.update(self.conn)?;
+
+        Ok(search)
+    }
+
+    pub fn delete_search(&self, name: &str) -> Result<()> {
+        let search = self.search(name)?;
+
+        self.require_owner(search.owner_id)?;
+
+        Ok(search.delete(self.conn)?)
+    }
+
+    //---
+    /// Recently deleted first.
+    pub fn trash(&self, amount: u32, page: u32) -> Result<Vec<Trash>> {
+        Ok(Trash::find_specific_amount_on_page(
+            self.session.access,
+            amount,
+            page,
+            self.conn,
+        )?)
+    }
+
+    /// Returns restored entry, whose name must be free again.
+    pub fn restore(&mut self, id: i32) -> Result<Trash> {
+        let trash = match Trash::find_by_id(id, self.conn)? {
+            Some(trash) if self.session.access.can_see(trash.owner_id, trash.shared) => trash,
+            _ => return Err(Error::TrashNotFound),
+        };
+
+        self.require_owner(trash.owner_id)?;
+
+        match trash.kind {
+            models::trash::Kind::File => self.require_free_file_name(&trash.name)?,
+            models::trash::Kind::Tag => self.require_free_tag_name(&trash.name)?,
+        }
+
+        trash.restore(&self.session.actor, self.conn)?;
+
+        match trash.kind {
+            models::trash::Kind::File => {
+                self.store_xattr(File::extract_from_name(&trash.name, self.conn)?.id)?
+            }
+            models::trash::Kind::Tag => {
+                self.store_xattr_of_tag(Tag::extract_from_name(&trash.name, self.conn)?.id)?
+            }
+        }
+
+        Ok(trash)
+    }
+
+    //---
+    /// Webhooks receive entries of the whole audit log, so admins only.
+    pub fn webhooks(&self) -> Result<Vec<Webhook>> {
+        self.require_admin()?;
+
+        Ok(Webhook::all(self.conn)?)
+    }
+
+    pub fn webhook(&self, name: &str) -> Result<Webhook> {
+        self.require_admin()?;
+
+        Webhook::extract_from_name(name, self.conn)
+    }
+
+    pub fn create_webhook(&self, hook: &Hook) -> Result<Webhook> {
+        self.require_admin()?;
+
+        if Webhook::name_exists(hook.name, self.conn)? {
+            return Err(Error::WebhookDuplication);
+        }
+
+        self.check_hook(hook)?;
+
+        Ok(Webhook::create(
+            hook.name,
+            hook.url,
+            hook.secret,
+            &hook.events(),
+            &hook.tags.join(","),
+            self.conn,
+        )?)
+    }
+
+    pub fn update_webhook(&self, name: &str, hook: &Hook) -> Result<Webhook> {
+        let mut webhook = self.webhook(name)?;
+
+        if webhook.name != hook.name && Webhook::name_exists(hook.name, self.conn)? {
+            return Err(Error::WebhookDuplication);
+        }
+
+        self.check_hook(hook)?;
+
+        webhook.name = hook.name.to_owned();
+        webhook.url = hook.url.to_owned();
+        webhook.events = hook.events();
+        webhook.tags = hook.tags.join(",");
+
+        if let Some(secret) = hook.secret {
+            webhook.secret = secret.to_owned();
+        }
+
+        webhook.update(self.conn)?;
+
+        Ok(webhook)
+    }
+
+    pub fn delete_webhook(&self, name: &str) -> Result<()> {
+        Ok(self.webhook(name)?.delete(self.conn)?)
+    }
+
+    //---
+    fn visible_collection(&self, name: &str) -> Result<Collection> {
+        match Collection::find_by_name(name, self.conn)? {
+            Some(collection)
+                if self
+                    .session
+                    .access
+                    .can_see(collection.owner_id, collection.shared) =>
+            {
+                Ok(collection)
+            }
+            _ => Err(Error::CollectionNotFound),
+        }
+    }
+
+    fn owned_collection(&self, name: &str) -> Result<Collection> {
+        let collection = self.visible_collection(name)?;
+
+        self.require_owner(collection.owner_id)?;
+
+        Ok(collection)
+    }
+
+    /// Deleted tags do not hide a search, its evaluation reports them instead.
+    fn sees_tags_of(&self, search: &SavedSearch) -> Result<bool> {
+        let names = search
+            .tags
+            .split(',')
+            .filter(|name| !name.is_empty())
+            .collect::<Vec<_>>();
+
+        Ok(
+            Tag::find_all_where_in_names(&names, Access::All, self.conn)?
+                .iter()
+                .all(|tag| self.session.access.can_see(tag.owner_id, tag.shared)),
+        )
+    }
+
+    fn require_free_tag_name(&self, name: &str) -> Result<()> {
+        self.require_free(
+            Tag::find_by_name(name, self.conn)?.map(|tag| (tag.owner_id, tag.shared)),
+            Error::TagDuplication,
+        )
+    }
+
+    fn require_free_file_name(&self, name: &str) -> Result<()> {
+        self.require_free(
+            File::find_by_name(name, self.conn)?.map(|file| (file.owner_id, file.shared)),
+            Error::FileDuplication,
+        )
+    }
+
+    /// `holder` is owner and sharing of the entry having the name, if any.
+    /// Private entries of other users are not revealed by `duplication`.
+    fn require_free(&self, holder: Option<(Option<i32>, bool)>, duplication: Error) -> Result<()> {
+        match holder {
+            Some((owner_id, shared)) if self.session.access.can_see(owner_id, shared) => {
+                Err(duplication)
+            }
+            Some(_) => Err(Error::NameTaken),
+            None => Ok(()),
+        }
+    }
+
+    fn check_hook(&self, hook: &Hook) -> Result<()> {
+        if !hook.url.starts_with("http://") && !hook.url.starts_with("https://") {
+            return Err(Error::WebhookInvalidUrl);
+        }
+
+        self.tags_by_names(hook.tags)?;
+
+        Ok(())
+    }
+
+    fn tags_ids<S>(&self, names: &[S]) -> Result<Vec<i32>>
+    where
+        S: AsRef<str> + ToString,
+    {
+        Ok(self
+            .tags_by_names(names)?
+            .iter()
+            .map(|tag| tag.id)
+            .collect())
+    }
+
+    fn file_query<'q>(&self, query: &Query, tags: &'q [i32]) -> search::FileQuery<'q> {
+        search::FileQuery {
+            tags,
+            exact: query.exact,
+            text: query.text.and_then(search::match_expression),
+            sort: query.sort,
+            access: self.session.access,
+        }
+    }
+
+    fn visible_file(&self, file: Option<File>) -> Result<File> {
+        match file {
+            Some(file) if self.session.access.can_see(file.owner_id, file.shared) => {
+                let mut files = vec![file];
+                File::load_tags(&mut files, self.session.access, self.conn)?;
+
+                Ok(files.pop().unwrap())
+            }
+            _ => Err(Error::FileNotFound),
+        }
+    }
+
+    /// Attribute gets shared tags and private ones of the file owner, whatever
+    /// the session sees, so it shows nothing the owner could not.
+    fn store_xattr(&self, file_id: i32) -> Result<()> {
+        if let (true, Some(mut file)) = (self.xattr, File::find_by_id(file_id, self.conn)?) {
+            let owner_id = file.owner_id;
+
+            file.update_tags(self.conn)?;
+            file.tags
+                .retain(|tag| tag.shared || tag.owner_id.is_none() || tag.owner_id == owner_id);
+
+            if let Err(err) = crate::xattr::store(&file) {
+                log::warn!("Cannot write tags of {}: {}", file.name, err);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn require_admin(&self) -> Result<()> {
+        if self.session.admin {
+            Ok(())
+        } else {
+            Err(Error::PermissionDenied)
+        }
+    }
+
+    fn store_xattr_of_tag(&self, tag_id: i32) -> Result<()> {
+        for file_id in self.files_ids_of_tag(tag_id)? {
+            self.store_xattr(file_id)?;
+        }
+
+        Ok(())
+    }
+
+    /// Empty unless attributes are written, they are the only use.
+    fn files_ids_of_tag(&self, tag_id: i32) -> Result<Vec<i32>> {
+        if !self.xattr {
+            return Ok(Vec::new());
+        }
+
+        Ok(
+            models::relationships::FileTag::all_for_tags_ids(std::iter::once(tag_id), self.conn)?
+                .iter()
+                .map(|relation| relation.file_id)
+                .collect(),
+        )
+    }
+
+    /// Unowned entries may be changed by anyone, owned ones only by their owner or an admin.
+    fn require_owner(&self, owner_id: Option<i32>) -> Result<()> {
+        match owner_id {
+            Some(owner_id) if !self.session.admin && self.session.user_id != Some(owner_id) => {
+                Err(Error::PermissionDenied)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn session(user_id: i32) -> Session {
+        Session {
+            user_id: Some(user_id),
+            access: Access::User(user_id),
+            admin: false,
+            actor: "test".to_owned(),
+        }
+    }
+
+    #[test]
+    fn repeated_tag_names_are_found() {
+        let mut conn = crate::get_conn(Path::new(":memory:")).unwrap();
+        let library = Library::new(&mut conn, Session::local("test"));
+        library.create_tag("cat", None, true).unwrap();
+
+        assert_eq!(library.tags_by_names(&["cat", "cat"]).unwrap().len(), 1);
+
+        match library.tags_by_names(&["dog", "cat", "dog"]) {
+            Err(Error::TagsNotFound(names)) => assert_eq!(names, ["dog"]),
+            res => panic!("unexpected {:?}", res.map(|tags| tags.len())),
+        }
+    }
+
+    #[test]
+    fn private_file_is_missing_before_relation_is_checked() {
+        let mut conn = crate::get_conn(Path::new(":memory:")).unwrap();
+        let file = Library::new(&mut conn, session(1))
+            .create_file("photo.jpg", &[] as &[&str], false)
+            .unwrap();
+        Library::new(&mut conn, Session::local("test"))
+            .create_tag("cat", None, true)
+            .unwrap();
+        Library::new(&mut conn, session(1))
+            .add_tag(file.id, "cat")
+            .unwrap();
+
+        match Library::new(&mut conn, session(2)).add_tag(file.id, "cat") {
+            Err(Error::FileNotFound) => {}
+            res => panic!("unexpected {:?}", res),
+        }
+    }
+
+    #[test]
+    fn collections_and_searches_are_scoped_by_owner() {
+        let mut conn = crate::get_conn(Path::new(":memory:")).unwrap();
+        let mut owner = Library::new(&mut conn, session(1));
+        owner.create_tag("secret", None, false).unwrap();
+        owner.create_collection("mine", &[], false).unwrap();
+        owner.create_collection("ours", &[], true).unwrap();
+        owner
+            .create_search(
+                "hidden",
+                &Query {
+                    tags: &["secret"],
+                    ..Query::default()
+                },
+                true,
+            )
+            .unwrap();
+
+        let mut other = Library::new(&mut conn, session(2));
+        let names = |collections: Vec<Collection>| {
+            collections
+                .into_iter()
+                .map(|collection| collection.name)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names(other.collections().unwrap()), ["ours"]);
+        assert!(matches!(
+            other.collection("mine"),
+            Err(Error::CollectionNotFound)
+        ));
+        assert!(matches!(
+            other.delete_collection("ours"),
+            Err(Error::PermissionDenied)
+        ));
+        assert!(matches!(
+            other.create_collection("mine", &[], true),
+            Err(Error::NameTaken)
+        ));
+        assert!(matches!(
+            other.create_tag("secret", None, true),
+            Err(Error::NameTaken)
+        ));
+        assert!(other.searches().unwrap().is_empty());
+        assert!(matches!(other.search("hidden"), Err(Error::SearchNotFound)));
+    }
+
+    #[test]
+    fn failed_atomically_keeps_nothing() {
+        let mut conn = crate::get_conn(Path::new(":memory:")).unwrap();
+        let mut library = Library::new(&mut conn, Session::local("test"));
+
+        let res = library.atomically(|library| {
+            library.create_tag("cat", None, true)?;
+            library.create_file("a.jpg", &["cat"], true)?;
+            library.create_file("a.jpg", &["cat"], true)
+        });
+
+        assert!(matches!(res, Err(Error::FileDuplication)));
+        assert!(library.tags().unwrap().is_empty());
+        assert!(matches!(library.file("a.jpg"), Err(Error::FileNotFound)));
+    }
+
+    #[test]
+    fn deleted_user_entries_stay_private_to_heir() {
+        let mut conn = crate::get_conn(Path::new(":memory:")).unwrap();
+        let gone = models::User::create("gone", models::Permission::Tag, &conn).unwrap();
+        let heir = models::User::create("heir", models::Permission::Tag, &conn).unwrap();
+
+        let mut library = Library::new(&mut conn, session(gone.id));
+        library.create_tag("secret", None, false).unwrap();
+        library.create_file("kept.jpg", &["secret"], false).unwrap();
+        library
+            .create_file("trashed.jpg", &["secret"], false)
+            .unwrap();
+        library.delete_file("trashed.jpg").unwrap();
+        library.create_collection("album", &[], false).unwrap();
+        library
+            .create_search("search", &Query::default(), false)
+            .unwrap();
+
+        gone.delete(&heir, &conn).unwrap();
+
+        let other = Library::new(&mut conn, session(heir.id + 1));
+
+        assert!(matches!(other.file("kept.jpg"), Err(Error::FileNotFound)));
+        assert!(matches!(other.tag("secret"), Err(Error::TagNotFound)));
+        assert!(other.collections().unwrap().is_empty());
+        assert!(other.searches().unwrap().is_empty());
+        assert!(other.trash(10, 0).unwrap().is_empty());
+
+        let library = Library::new(&mut conn, session(heir.id));
+
+        assert_eq!(library.file("kept.jpg").unwrap().owner_id, Some(heir.id));
+        assert_eq!(library.tag("secret").unwrap().owner_id, Some(heir.id));
+        assert_eq!(library.collections().unwrap().len(), 1);
+        assert_eq!(library.searches().unwrap().len(), 1);
+        assert_eq!(library.trash(10, 0).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn trash_and_collections_nest_in_atomically() {
+        let mut conn = crate::get_conn(Path::new(":memory:")).unwrap();
+        let mut library = Library::new(&mut conn, Session::local("test"));
+
+        library
+            .atomically(|library| {
+                let file = library.create_file("a.jpg", &[] as &[&str], true)?;
+                library.create_file("b.jpg", &[] as &[&str], true)?;
+                library.create_collection("album", &[file.id], true)?;
+                library.delete_file("b.jpg")
+            })
+            .unwrap();
+
+        assert_eq!(library.collection("album").unwrap().files.len(), 1);
+        assert_eq!(library.trash(10, 0).unwrap().len(), 1);
+
+        let res = library.atomically(|library| {
+            library.delete_file("a.jpg")?;
+            library.create_collection("album", &[], true)
+        });
+
+        assert!(matches!(res, Err(Error::CollectionDuplication)));
+        assert!(library.file("a.jpg").is_ok());
+        assert_eq!(library.trash(10, 0).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn text_search_ignores_private_tags_of_others() {
+        let mut conn = crate::get_conn(Path::new(":memory:")).unwrap();
+        let mut owner = Library::new(&mut conn, session(1));
+        owner
+            .create_tag("hidden", Some("confidential"), false)
+            .unwrap();
+        owner.create_file("photo.jpg", &["hidden"], true).unwrap();
+
+        let found = |library: &Library, text| {
+            let query = Query {
+                text: Some(text),
+                ..Query::default()
+            };
+
+            library.files(&query, 10, 0).unwrap().len()
+        };
+
+        assert_eq!(found(&owner, "hidden"), 1);
+        assert_eq!(found(&owner, "confidential"), 1);
+
+        let other = Library::new(&mut conn, session(2));
+
+        assert_eq!(found(&other, "photo"), 1);
+        assert_eq!(found(&other, "hidden"), 0);
+        assert_eq!(found(&other, "confidential"), 0);
+    }
+
+    #[test]
+    fn only_owner_changes_tags_of_shared_file() {
+        let mut conn = crate::get_conn(Path::new(":memory:")).unwrap();
+        let mut owner = Library::new(&mut conn, session(1));
+        owner.create_tag("cat", None, true).unwrap();
+        owner.create_tag("dog", None, true).unwrap();
+        let file = owner.create_file("photo.jpg", &["cat"], true).unwrap();
+
+        let other = Library::new(&mut conn, session(2));
+
+        assert!(matches!(
+            other.add_tag(file.id, "dog"),
+            Err(Error::PermissionDenied)
+        ));
+        assert!(matches!(
+            other.remove_tag(file.id, "cat"),
+            Err(Error::PermissionDenied)
+        ));
+
+        let owner = Library::new(&mut conn, session(1));
+        owner.add_tag(file.id, "dog").unwrap();
+        owner.remove_tag(file.id, "cat").unwrap();
+
+        Library::new(&mut conn, Session::local("admin"))
+            .add_tag(file.id, "cat")
+            .unwrap();
+    }
+
+    #[test]
+    fn relation_removed_along_with_file_stays_visible() {
+        let mut conn = crate::get_conn(Path::new(":memory:")).unwrap();
+        let mut owner = Library::new(&mut conn, session(1));
+        owner.create_tag("cat", None, true).unwrap();
+        owner.create_file("shared.jpg", &["cat"], true).unwrap();
+        owner.create_file("private.jpg", &["cat"], false).unwrap();
+        owner.delete_file("shared.jpg").unwrap();
+        owner.delete_file("private.jpg").unwrap();
+
+        let removed = models::Audit::find_specific_amount_after_id(0, 100, &conn)
+            .unwrap()
+            .into_iter()
+            .filter(|entry| entry.action == audit::Action::FileTagDelete)
+            .filter(|entry| entry.visible(Access::User(2), &conn).unwrap())
+            .map(|entry| entry.before.unwrap().0["file"].clone())
+            .collect::<Vec<_>>();
+
+        assert_eq!(removed, ["shared.jpg"]);
+    }
+
+    #[test]
+    fn restored_file_goes_back_to_its_collections() {
+        let mut conn = crate::get_conn(Path::new(":memory:")).unwrap();
+        let mut library = Library::new(&mut conn, Session::local("test"));
+        let mut files = Vec::new();
+
+        for name in &["a.jpg", "b.jpg", "c.jpg"] {
+            files.push(library.create_file(name, &[] as &[&str], true).unwrap().id);
+        }
+
+        library.create_collection("album", &files, true).unwrap();
+        library.delete_file("b.jpg").unwrap();
+
+        let names = |library: &Library| {
+            library
+                .collection("album")
+                .unwrap()
+                .files
+                .into_iter()
+                .map(|file| file.name)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names(&library), ["a.jpg", "c.jpg"]);
+
+        let trash = library.trash(10, 0).unwrap();
+        library.restore(trash[0].id).unwrap();
+
+        assert_eq!(names(&library), ["a.jpg", "b.jpg", "c.jpg"]);
+    }
+}
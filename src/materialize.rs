@@ -0,0 +1,369 @@
+//! Directory of symlinks to files organized by tags, `<dir>/cat/dog/one.png`
+//! links every file having both tags. Tags of a path go in sorted order, so
+//! there is no `dog/cat`. Directories go `depth` tags deep and exist only
+//! while they have files.
+//!
+//! Links made are kept in a manifest inside the directory along with the last
+//! seen audit entry, so the next run only redoes files changed since then.
+
+use crate::{
+    library::{self, Library, Query, Session},
+    models::{audit, Audit},
+    Connection, SqlError,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{Display, Formatter, Result as FmtResult},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+pub const MANIFEST: &str = ".tagz-materialize.json";
+const BATCH: u32 = 500;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Library(library::Error),
+    Manifest(serde_json::Error),
+    /// directory has entries but no manifest, so it was not made by us
+    NotEmpty(PathBuf),
+}
+
+impl Display for Error {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        match self {
+            Error::Io(err) => Display::fmt(err, fmt),
+            Error::Library(err) => Display::fmt(err, fmt),
+            Error::Manifest(err) => write!(fmt, "broken {}: {}", MANIFEST, err),
+            Error::NotEmpty(dir) => write!(
+                fmt,
+                "{} is not empty and was not materialized before",
+                dir.display()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<library::Error> for Error {
+    fn from(err: library::Error) -> Self {
+        Error::Library(err)
+    }
+}
+
+impl From<SqlError> for Error {
+    fn from(err: SqlError) -> Self {
+        Error::Library(err.into())
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Manifest(err)
+    }
+}
+
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+struct Manifest {
+    /// last applied audit entry
+    cursor: i64,
+    depth: u32,
+    /// links of every file, relative to the directory
+    links: BTreeMap<i32, Vec<PathBuf>>,
+}
+
+/// Brings `dir` up to date, returns number of files relinked.
+pub fn run(dir: &Path, depth: u32, conn: &mut Connection) -> Result<usize> {
+    fs::create_dir_all(dir)?;
+
+    let manifest = match fs::read(dir.join(MANIFEST)) {
+        Ok(bytes) => Some(serde_json::from_slice::<Manifest>(&bytes)?),
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+            if fs::read_dir(dir)?.next().is_some() {
+                return Err(Error::NotEmpty(dir.to_owned()));
+            }
+
+            None
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    let mut manifest = match manifest {
+        Some(manifest) if manifest.depth == depth => manifest,
+        Some(manifest) => {
+            clear(dir, manifest)?;
+
+            return rebuild(dir, depth, conn);
+        }
+        None => return rebuild(dir, depth, conn),
+    };
+
+    let mut changed = BTreeSet::new();
+
+    loop {
+        let entries = Audit::find_specific_amount_after_id(manifest.cursor, BATCH, conn)?;
+
+        for entry in &entries {
+            match entry.action {
+                // every file of the tag moves, simpler to start over
+                audit::Action::TagUpdate => {
+                    clear(dir, manifest)?;
+
+                    return rebuild(dir, depth, conn);
+                }
+                audit::Action::FileCreate
+                | audit::Action::FileDelete
+                | audit::Action::FileRestore => {
+                    changed.extend(file_id(entry, "id"));
+                }
+                audit::Action::FileTagCreate | audit::Action::FileTagDelete => {
+                    changed.extend(file_id(entry, "file_id"));
+                }
+                _ => {}
+            }
+        }
+
+        match entries.last() {
+            Some(entry) => manifest.cursor = entry.id,
+            None => break,
+        }
+    }
+
+    let library = Library::new(conn, Session::local("materialize"));
+
+    for id in &changed {
+        if let Some(links) = manifest.links.remove(id) {
+            unlink(dir, &links)?;
+        }
+
+        match library.file_by_id(*id) {
+            Ok(file) => {
+                manifest.links.insert(*id, link(dir, &file, depth)?);
+            }
+            Err(library::Error::FileNotFound) => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    save(dir, &manifest)?;
+
+    Ok(changed.len())
+}
+
+fn rebuild(dir: &Path, depth: u32, conn: &mut Connection) -> Result<usize> {
+    let mut manifest = Manifest {
+        cursor: Audit::last_id(conn)?,
+        depth,
+        links: BTreeMap::new(),
+    };
+
+    let library = Library::new(conn, Session::local("materialize"));
+
+    for page in 0.. {
+        let files = library.files(&Query::default(), BATCH, page)?;
+
+        for file in &files {
+            manifest.links.insert(file.id, link(dir, file, depth)?);
+        }
+
+        if files.len() < BATCH as usize {
+            break;
+        }
+    }
+
+    log::info!(
+        "Materialized {} files into {}.",
+        manifest.links.len(),
+        dir.display()
+    );
+
+    save(dir, &manifest)?;
+
+    Ok(manifest.links.len())
+}
+
+fn clear(dir: &Path, manifest: Manifest) -> Result<()> {
+    for links in manifest.links.values() {
+        unlink(dir, links)?;
+    }
+
+    Ok(())
+}
+
+fn save(dir: &Path, manifest: &Manifest) -> Result<()> {
+    let path = dir.join(MANIFEST);
+    let tmp = path.with_extension("tmp");
+
+    fs::write(&tmp, serde_json::to_vec(manifest)?)?;
+    fs::rename(tmp, path)?;
+
+    Ok(())
+}
+
+fn file_id(entry: &Audit, field: &str) -> Option<i32> {
+    entry
+        .after
+        .as_ref()
+        .or_else(|| entry.before.as_ref())
+        .and_then(|snapshot| snapshot.0.get(field))
+        .and_then(|id| id.as_i64())
+        .map(|id| id as i32)
+}
+
+/// Links file into every directory made of its tags in sorted order.
+fn link(dir: &Path, file: &crate::models::File, depth: u32) -> io::Result<Vec<PathBuf>> {
+    let base = component(
+        Path::new(&file.name)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(&file.name),
+    );
+    let tags = file
+        .tags
+        .iter()
+        .map(|tag| component(&tag.name))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+
+    let mut links = Vec::new();
+    // directory along with index of the first tag it may be followed by
+    let mut level = vec![(PathBuf::new(), 0)];
+
+    for _ in 0..depth {
+        let mut next = Vec::new();
+
+        for (parent, first) in &level {
+            for (index, tag) in tags.iter().enumerate().skip(*first) {
+                let path = parent.join(tag);
+                fs::create_dir_all(dir.join(&path))?;
+
+                let mut entry = path.join(&base);
+
+                if fs::symlink_metadata(dir.join(&entry)).is_ok() {
+                    entry = path.join(format!("{} ({})", base, file.id));
+                    fs::remove_file(dir.join(&entry)).ok();
+                }
+
+                symlink(&file.name, &dir.join(&entry))?;
+                links.push(entry);
+                next.push((path, index + 1));
+            }
+        }
+
+        level = next;
+    }
+
+    Ok(links)
+}
+
+/// Removes links and directories left empty by them.
+fn unlink(dir: &Path, links: &[PathBuf]) -> io::Result<()> {
+    for link in links {
+        if let Err(err) = fs::remove_file(dir.join(link)) {
+            if err.kind() != io::ErrorKind::NotFound {
+                return Err(err);
+            }
+        }
+
+        let mut parent = link.parent();
+
+        while let Some(path) = parent.filter(|path| !path.as_os_str().is_empty()) {
+            if fs::remove_dir(dir.join(path)).is_err() {
+                break;
+            }
+
+            parent = path.parent();
+        }
+    }
+
+    Ok(())
+}
+
+/// Tag or file name usable as single path component.
+fn component(name: &str) -> String {
+    match name.replace('/', "_") {
+        ref name if name == "." || name == ".." || name == MANIFEST => ["_", name].concat(),
+        name => name,
+    }
+}
+
+#[cfg(unix)]
+fn symlink(target: &str, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn symlink(target: &str, link: &Path) -> io::Result<()> {
+    std::os::windows::fs::symlink_file(target, link)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn linked(dir: &Path, path: &str) -> bool {
+        fs::symlink_metadata(dir.join(path)).is_ok()
+    }
+
+    #[test]
+    fn links_sorted_combinations_and_follows_audit() {
+        let dir = std::env::temp_dir().join(format!("tagz-materialize-{}", std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+
+        let mut conn = crate::get_conn(Path::new(":memory:")).unwrap();
+        let mut library = Library::new(&mut conn, Session::local("test"));
+
+        for tag in &["cat", "dog", "fox"] {
+            library.create_tag(tag, None, true).unwrap();
+        }
+
+        library
+            .create_file("/photos/one.png", &["dog", "cat"], true)
+            .unwrap();
+        let two = library
+            .create_file("/photos/two.png", &["fox"], true)
+            .unwrap();
+
+        // rebuild
+        assert_eq!(run(&dir, 2, &mut conn).unwrap(), 2);
+        assert!(linked(&dir, "cat/one.png"));
+        assert!(linked(&dir, "dog/one.png"));
+        assert!(linked(&dir, "cat/dog/one.png"));
+        assert!(!dir.join("dog/cat").exists());
+        assert!(!dir.join("cat/cat").exists());
+        assert!(linked(&dir, "fox/two.png"));
+
+        // incremental path relinks only files changed past the cursor
+        let mut library = Library::new(&mut conn, Session::local("test"));
+        library.add_tag(two.id, "cat").unwrap();
+        library.delete_file("/photos/one.png").unwrap();
+
+        assert_eq!(run(&dir, 2, &mut conn).unwrap(), 2);
+        assert!(linked(&dir, "cat/fox/two.png"));
+        assert!(!dir.join("fox/cat").exists());
+        assert!(!dir.join("dog").exists());
+        assert!(!linked(&dir, "cat/one.png"));
+
+        let manifest =
+            serde_json::from_slice::<Manifest>(&fs::read(dir.join(MANIFEST)).unwrap()).unwrap();
+
+        assert_eq!(manifest.cursor, Audit::last_id(&conn).unwrap());
+        assert_eq!(manifest.links.keys().collect::<Vec<_>>(), [&two.id]);
+
+        // nothing changed since
+        assert_eq!(run(&dir, 2, &mut conn).unwrap(), 0);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,248 @@
+use super::*;
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, ValueRef};
+use serde_json::Value as JsonValue;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Action {
+    #[serde(rename = "file.create")]
+    FileCreate,
+    #[serde(rename = "file.delete")]
+    FileDelete,
+    /// file taken back from trash
+    #[serde(rename = "file.restore")]
+    FileRestore,
+    #[serde(rename = "tag.create")]
+    TagCreate,
+    /// tag renamed
+    #[serde(rename = "tag.update")]
+    TagUpdate,
+    #[serde(rename = "tag.delete")]
+    TagDelete,
+    #[serde(rename = "tag.restore")]
+    TagRestore,
+    /// tag added to file
+    #[serde(rename = "file_tag.create")]
+    FileTagCreate,
+    /// tag removed from file
+    #[serde(rename = "file_tag.delete")]
+    FileTagDelete,
+}
+
+impl Action {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Action::FileCreate => "file.create",
+            Action::FileDelete => "file.delete",
+            Action::FileRestore => "file.restore",
+            Action::TagCreate => "tag.create",
+            Action::TagUpdate => "tag.update",
+            Action::TagDelete => "tag.delete",
+            Action::TagRestore => "tag.restore",
+            Action::FileTagCreate => "file_tag.create",
+            Action::FileTagDelete => "file_tag.delete",
+        }
+    }
+}
+
+impl FromStr for Action {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "file.create" => Action::FileCreate,
+            "file.delete" => Action::FileDelete,
+            "file.restore" => Action::FileRestore,
+            "tag.create" => Action::TagCreate,
+            "tag.update" => Action::TagUpdate,
+            "tag.delete" => Action::TagDelete,
+            "tag.restore" => Action::TagRestore,
+            "file_tag.create" => Action::FileTagCreate,
+            "file_tag.delete" => Action::FileTagDelete,
+            _ => return Err(()),
+        })
+    }
+}
+
+impl ToSql for Action {
+    fn to_sql(&self) -> SqlResult<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for Action {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()
+            .and_then(|s| s.parse().map_err(|_| FromSqlError::InvalidType))
+    }
+}
+
+/// JSON copy of an entry stored as text.
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(transparent)]
+pub struct Snapshot(pub JsonValue);
+
+impl ToSql for Snapshot {
+    fn to_sql(&self) -> SqlResult<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.0.to_string()))
+    }
+}
+
+impl FromSql for Snapshot {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value.as_str().and_then(|s| {
+            serde_json::from_str(s)
+                .map(Snapshot)
+                .map_err(|err| FromSqlError::Other(Box::new(err)))
+        })
+    }
+}
+
+pub fn snapshot<T>(value: &T) -> Option<Snapshot>
+where
+    T: serde::Serialize,
+{
+    serde_json::to_value(value).ok().map(Snapshot)
+}
+
+/// Entry of append-only log of mutations, `id` is increasing.
+#[derive(Clone, Debug, FromRow, serde::Serialize)]
+pub struct Audit {
+    pub id: i64,
+    pub actor: String,
+    pub action: Action,
+    pub before: Option<Snapshot>,
+    pub after: Option<Snapshot>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Default)]
+pub struct Filter<'a> {
+    pub actor: Option<&'a str>,
+    pub action: Option<Action>,
+    pub since: Option<NaiveDateTime>,
+    pub until: Option<NaiveDateTime>,
+}
+
+impl Audit {
+    pub fn record(
+        actor: &str,
+        action: Action,
+        before: Option<Snapshot>,
+        after: Option<Snapshot>,
+        conn: &Connection,
+    ) -> SqlResult<()> {
+        conn.execute(
+            "INSERT INTO `audit` (actor, action, before, after) VALUES(?1, ?2, ?3, ?4)",
+            params! {actor, action, before, after},
+        )
+        .map(|_| ())
+    }
+
+    /// Oldest first, used to follow the log.
+    pub fn find_specific_amount_after_id(
+        id: i64,
+        amount: u32,
+        conn: &Connection,
+    ) -> SqlResult<Vec<Self>> {
+        conn.prepare("SELECT * FROM `audit` WHERE `id`>?1 ORDER BY `id` ASC LIMIT ?2")?
+            .query_map(params! { id, amount }, FromRow::from_row)?
+            .collect()
+    }
+
+    pub fn last_id(conn: &Connection) -> SqlResult<i64> {
+        conn.query_row(
+            "SELECT COALESCE(MAX(`id`), 0) FROM `audit`",
+            params! {},
+            |row| row.get(0),
+        )
+    }
+
+    /// Checks snapshot ownership, relations are visible when both of their
+    /// sides were.
+    pub fn visible(&self, access: Access, conn: &Connection) -> SqlResult<bool> {
+        if access == Access::All {
+            return Ok(true);
+        }
+
+        let snapshot = match self.after.as_ref().or_else(|| self.before.as_ref()) {
+            Some(snapshot) => &snapshot.0,
+            None => return Ok(false),
+        };
+        let id = |key: &str| snapshot[key].as_i64().map(|id| id as i32);
+        let shared = |key: &str| snapshot[key].as_bool();
+
+        match self.action {
+            Action::FileTagCreate | Action::FileTagDelete => {
+                match (shared("file_shared"), shared("tag_shared")) {
+                    (Some(file_shared), Some(tag_shared)) => Ok(access
+                        .can_see(id("file_owner_id"), file_shared)
+                        && access.can_see(id("tag_owner_id"), tag_shared)),
+                    // recorded without ownership of its sides, which must still exist
+                    _ => {
+                        let file = match id("file_id") {
+                            Some(file_id) => File::find_by_id(file_id, conn)?,
+                            None => None,
+                        };
+                        let tag = match id("tag_id") {
+                            Some(tag_id) => Tag::find_by_id(tag_id, conn)?,
+                            None => None,
+                        };
+
+                        Ok(match (file, tag) {
+                            (Some(file), Some(tag)) => {
+                                access.can_see(file.owner_id, file.shared)
+                                    && access.can_see(tag.owner_id, tag.shared)
+                            }
+                            _ => false,
+                        })
+                    }
+                }
+            }
+            _ => Ok(access.can_see(
+                id("owner_id"),
+                snapshot["shared"].as_bool().unwrap_or(false),
+            )),
+        }
+    }
+
+    /// Newest first.
+    pub fn find_specific_amount_by_filter_on_page(
+        filter: &Filter,
+        amount: u32,
+        page: u32,
+        conn: &Connection,
+    ) -> SqlResult<Vec<Self>> {
+        let mut sql = String::from("SELECT * FROM `audit` WHERE 1");
+        let mut params: search::NamedParams = Vec::with_capacity(6);
+
+        if let Some(actor) = filter.actor {
+            sql.push_str(" AND `actor`=:actor");
+            params.push((":actor", Box::new(actor.to_owned())));
+        }
+
+        if let Some(action) = filter.action {
+            sql.push_str(" AND `action`=:action");
+            params.push((":action", Box::new(action)));
+        }
+
+        if let Some(since) = filter.since {
+            sql.push_str(" AND `created_at`>=datetime(:since)");
+            params.push((":since", Box::new(since)));
+        }
+
+        if let Some(until) = filter.until {
+            sql.push_str(" AND `created_at`<datetime(:until)");
+            params.push((":until", Box::new(until)));
+        }
+
+        sql.push_str(" ORDER BY `id` DESC LIMIT :limit OFFSET :offset");
+        params.push((":limit", Box::new(amount)));
+        params.push((":offset", Box::new(amount * page)));
+
+        conn.prepare(&sql)?
+            .query_map_named(&search::borrow_params(&params), FromRow::from_row)?
+            .collect()
+    }
+}
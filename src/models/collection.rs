@@ -0,0 +1,257 @@
+use super::*;
+
+#[derive(serde::Serialize, FromRow)]
+pub struct Collection {
+    #[serde(skip)]
+    pub id: i32,
+    pub name: String,
+    pub owner_id: Option<i32>,
+    pub shared: bool,
+
+    #[field_default]
+    pub files: Vec<File>,
+    pub updated_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
+
+impl Collection {
+    pub fn create_with_files<N>(
+        name: N,
+        files: &[i32],
+        owner_id: Option<i32>,
+        shared: bool,
+        conn: &Connection,
+    ) -> SqlResult<Self>
+    where
+        N: ToSql,
+    {
+        atomically(conn, || {
+            conn.execute(
+                "INSERT INTO `collections` (name, owner_id, shared) VALUES(?1, ?2, ?3)",
+                params! {name, owner_id, shared},
+            )?;
+
+            let inst: Self = tagz_cg_serv::last_inserted!(conn, "collections")?;
+            let mut stmt = conn.prepare(
+                "INSERT INTO `collection_files` (collection_id, file_id, position) VALUES(?1, ?2, ?3)",
+            )?;
+
+            for (position, file) in files.iter().enumerate() {
+                stmt.execute(params![inst.id, file, position as i64])?;
+            }
+
+            drop(stmt);
+
+            Ok(inst)
+        })
+    }
+
+    pub fn rename<N>(&mut self, name: N, conn: &Connection) -> SqlResult<()>
+    where
+        N: ToSql + ToString,
+    {
+        conn.execute(
+            "UPDATE `collections` SET name=?2, updated_at=CURRENT_TIMESTAMP WHERE `id`=?1",
+            params! {self.id, name},
+        )?;
+
+        self.name = name.to_string();
+
+        Ok(())
+    }
+
+    pub fn delete(&self, conn: &Connection) -> SqlResult<()> {
+        conn.execute(
+            "DELETE FROM `collection_files` WHERE `collection_id`=?1",
+            params! { self.id },
+        )?;
+        conn.execute(
+            "DELETE FROM `collections` WHERE `id`=?1",
+            params! { self.id },
+        )
+        .map(|_| ())
+    }
+
+    pub fn extract_from_name<N>(name: N, conn: &Connection) -> library::Result<Self>
+    where
+        N: ToSql,
+    {
+        Self::find_by_name(name, conn)?.ok_or(library::Error::CollectionNotFound)
+    }
+
+    pub fn find_by_id(id: i32, conn: &Connection) -> SqlResult<Option<Self>> {
+        conn.prepare("SELECT * FROM `collections` WHERE `id`=?1 LIMIT 1")?
+            .query_row(params! {id}, FromRow::from_row)
+            .optional()
+    }
+
+    /// `(collection_id, position)` of every collection having the file.
+    pub fn memberships_of(file_id: i32, conn: &Connection) -> SqlResult<Vec<(i32, u32)>> {
+        conn.prepare(
+            "SELECT `collection_id`, `position` FROM `collection_files` WHERE `file_id`=?1",
+        )?
+        .query_map(params! {file_id}, |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect()
+    }
+
+    pub fn find_by_name<N>(name: N, conn: &Connection) -> SqlResult<Option<Self>>
+    where
+        N: ToSql,
+    {
+        conn.prepare("SELECT * FROM `collections` WHERE `name`=?1 LIMIT 1")?
+            .query_row(params! {name}, FromRow::from_row)
+            .optional()
+    }
+
+    pub fn name_exists<N>(name: N, conn: &Connection) -> SqlResult<bool>
+    where
+        N: ToSql,
+    {
+        conn.prepare("SELECT 1 FROM `collections` WHERE `name`=?1 LIMIT 1")?
+            .query_row(params! {name}, |row| row.get(0))
+            .optional()
+            .map(|x: Option<i32>| x.is_some())
+    }
+
+    pub fn all(access: Access, conn: &Connection) -> SqlResult<Vec<Self>> {
+        conn.prepare(
+            &[
+                "SELECT * FROM `collections` WHERE ",
+                &access.condition("collections"),
+                " ORDER BY `name` ASC",
+            ]
+            .concat(),
+        )?
+        .query_map(params! {}, Self::from_row)?
+        .collect()
+    }
+
+    /// Loads files visible with `access`, in collection order.
+    pub fn update_files(&mut self, access: Access, conn: &Connection) -> SqlResult<()> {
+        self.files = conn
+            .prepare(&[
+                "SELECT `files`.* FROM `collection_files` INNER JOIN `files` ON `files`.`id`=`file_id` WHERE `collection_id`=?1 AND ",
+                &access.condition("files"),
+                " ORDER BY `position` ASC",
+            ].concat())?
+            .query_map(params! {self.id}, FromRow::from_row)?
+            .collect::<SqlResult<Vec<File>>>()?;
+
+        File::load_tags(&mut self.files, access, conn)
+    }
+
+    pub fn files_ids(&self, conn: &Connection) -> SqlResult<Vec<i32>> {
+        conn.prepare(
+            "SELECT `file_id` FROM `collection_files` WHERE `collection_id`=?1 ORDER BY `position` ASC",
+        )?
+        .query_map(params! {self.id}, |row| row.get(0))?
+        .collect()
+    }
+
+    pub fn position_of(&self, file_id: i32, conn: &Connection) -> SqlResult<Option<u32>> {
+        conn.prepare(
+            "SELECT `position` FROM `collection_files` WHERE `collection_id`=?1 AND `file_id`=?2 LIMIT 1",
+        )?
+        .query_row(params! {self.id, file_id}, |row| row.get(0))
+        .optional()
+    }
+
+    pub fn len(&self, conn: &Connection) -> SqlResult<u32> {
+        conn.query_row(
+            "SELECT COUNT(*) FROM `collection_files` WHERE `collection_id`=?1",
+            params! {self.id},
+            |row| row.get(0),
+        )
+    }
+
+    /// Inserts file at `position` (appends if `None` or past the end), shifting the following ones.
+    pub fn insert_file(
+        &self,
+        file_id: i32,
+        position: Option<u32>,
+        conn: &Connection,
+    ) -> SqlResult<u32> {
+        atomically(conn, || {
+            let len = self.len(conn)?;
+            let position = position.map_or(len, |p| p.min(len));
+
+            conn.execute(
+                "UPDATE `collection_files` SET position=position + 1 WHERE `collection_id`=?1 AND `position`>=?2",
+                params! {self.id, position},
+            )?;
+            conn.execute(
+                "INSERT INTO `collection_files` (collection_id, file_id, position) VALUES(?1, ?2, ?3)",
+                params! {self.id, file_id, position},
+            )?;
+            conn.execute(
+                "UPDATE `collections` SET updated_at=CURRENT_TIMESTAMP WHERE `id`=?1",
+                params! {self.id},
+            )?;
+
+            Ok(position)
+        })
+    }
+
+    /// Following files are shifted back by `collection_files_compact` trigger.
+    pub fn remove_file(&self, file_id: i32, conn: &Connection) -> SqlResult<()> {
+        conn.execute(
+            "DELETE FROM `collection_files` WHERE `collection_id`=?1 AND `file_id`=?2",
+            params! {self.id, file_id},
+        )?;
+        conn.execute(
+            "UPDATE `collections` SET updated_at=CURRENT_TIMESTAMP WHERE `id`=?1",
+            params! {self.id},
+        )
+        .map(|_| ())
+    }
+
+    /// Moves file from `from` to `to` (clamped to the last position), shifting files in between.
+    pub fn move_file(&self, file_id: i32, from: u32, to: u32, conn: &Connection) -> SqlResult<u32> {
+        atomically(conn, || {
+            let to = to.min(self.len(conn)?.saturating_sub(1));
+
+            if to > from {
+                conn.execute(
+                    "UPDATE `collection_files` SET position=position - 1 WHERE `collection_id`=?1 AND `position`>?2 AND `position`<=?3",
+                    params! {self.id, from, to},
+                )?;
+            } else if to < from {
+                conn.execute(
+                    "UPDATE `collection_files` SET position=position + 1 WHERE `collection_id`=?1 AND `position`>=?3 AND `position`<?2",
+                    params! {self.id, from, to},
+                )?;
+            }
+
+            conn.execute(
+                "UPDATE `collection_files` SET position=?3 WHERE `collection_id`=?1 AND `file_id`=?2",
+                params! {self.id, file_id, to},
+            )?;
+            conn.execute(
+                "UPDATE `collections` SET updated_at=CURRENT_TIMESTAMP WHERE `id`=?1",
+                params! {self.id},
+            )?;
+
+            Ok(to)
+        })
+    }
+
+    /// `files` must contain exactly the files already in the collection.
+    pub fn reorder(&self, files: &[i32], conn: &Connection) -> SqlResult<()> {
+        atomically(conn, || {
+            let mut stmt = conn.prepare(
+                "UPDATE `collection_files` SET position=?3 WHERE `collection_id`=?1 AND `file_id`=?2",
+            )?;
+
+            for (position, file) in files.iter().enumerate() {
+                stmt.execute(params![self.id, file, position as i64])?;
+            }
+
+            drop(stmt);
+            conn.execute(
+                "UPDATE `collections` SET updated_at=CURRENT_TIMESTAMP WHERE `id`=?1",
+                params! {self.id},
+            )
+            .map(|_| ())
+        })
+    }
+}
@@ -1,13 +1,14 @@
 use super::*;
-use serde::ser::{SerializeSeq, Serializer};
 use std::collections::BTreeMap;
 
-#[derive(serde::Serialize, FromRow)]
+#[derive(Clone, serde::Serialize, FromRow)]
+#[serde(into = "tagz_types::File")]
 pub struct File {
     pub id: i32,
     pub name: String,
+    pub owner_id: Option<i32>,
+    pub shared: bool,
 
-    #[serde(serialize_with = "serialize_tags_vec")]
     #[field_default]
     pub tags: Vec<Tag>,
     pub updated_at: NaiveDateTime,
@@ -15,75 +16,127 @@ pub struct File {
 }
 
 macro_rules! insert {
-    ($conn:expr, $name:expr) => {
+    ($conn:expr, $name:expr, $owner_id:expr, $shared:expr) => {
         $conn.execute(
-            "INSERT INTO `files` (name) VALUES(?1)",
+            "INSERT INTO `files` (name, owner_id, shared) VALUES(?1, ?2, ?3)",
             params! {
-                $name
+                $name, $owner_id, $shared
             },
         )
     };
 }
 
+impl From<File> for tagz_types::File {
+    fn from(file: File) -> Self {
+        Self {
+            id: file.id,
+            name: file.name,
+            owner_id: file.owner_id,
+            shared: file.shared,
+            tags: file.tags.into_iter().map(|tag| tag.name).collect(),
+            updated_at: file.updated_at,
+            created_at: file.created_at,
+        }
+    }
+}
+
 impl File {
-    pub fn create_with_tags<P>(name: P, tags: &[i32], conn: &mut Connection) -> SqlResult<Self>
+    pub fn create_with_tags<P>(
+        name: P,
+        tags: &[i32],
+        owner_id: Option<i32>,
+        shared: bool,
+        actor: &str,
+        conn: &Connection,
+    ) -> SqlResult<Self>
     where
         P: ToSql,
     {
-        insert!(conn, name)?;
-
-        let inst = tagz_cg_serv::last_inserted!(&conn, "files")?;
+        atomically(conn, || {
+            insert!(conn, name, owner_id, shared)?;
 
-        let tx = conn.transaction()?;
-        let mut stmt = tx.prepare("INSERT INTO `file_tags` (file_id, tag_id) VALUES(?1, ?2)")?;
+            let mut inst: Self = tagz_cg_serv::last_inserted!(&conn, "files")?;
+            let mut stmt =
+                conn.prepare("INSERT INTO `file_tags` (file_id, tag_id) VALUES(?1, ?2)")?;
 
-        for tag in tags {
-            stmt.execute(params![inst.id, tag])?;
-        }
+            for tag in tags {
+                stmt.execute(params![inst.id, tag])?;
+            }
 
-        drop(stmt);
-        tx.commit()?;
+            inst.update_tags(conn)?;
+            Audit::record(
+                actor,
+                audit::Action::FileCreate,
+                None,
+                audit::snapshot(&inst),
+                conn,
+            )?;
 
-        Ok(inst)
+            Ok(inst)
+        })
     }
 
-    pub fn create<P>(name: P, conn: &Connection) -> SqlResult<Self>
+    pub fn create<P>(
+        name: P,
+        owner_id: Option<i32>,
+        shared: bool,
+        actor: &str,
+        conn: &Connection,
+    ) -> SqlResult<Self>
     where
         P: ToSql,
     {
-        insert!(conn, name)?;
+        atomically(conn, || {
+            insert!(conn, name, owner_id, shared)?;
+
+            let inst = tagz_cg_serv::last_inserted!(&conn, "files")?;
+            Audit::record(
+                actor,
+                audit::Action::FileCreate,
+                None,
+                audit::snapshot(&inst),
+                conn,
+            )?;
 
-        tagz_cg_serv::last_inserted!(&conn, "files")
+            Ok(inst)
+        })
     }
 
-    pub fn delete(&self, conn: &Connection) -> SqlResult<()> {
-        conn.execute("DELETE FROM `files` WHERE `id`=?1", params! { self.id })
-            .map(|_| ())
-    }
+    pub fn delete(&self, actor: &str, conn: &Connection) -> SqlResult<()> {
+        atomically(conn, || {
+            conn.execute("DELETE FROM `files` WHERE `id`=?1", params! { self.id })?;
 
-    pub fn unlink_all_tags(&self, conn: &Connection) -> SqlResult<()> {
-        conn.execute(
-            "DELETE FROM `file_tags` WHERE `file_id`=?1",
-            params! { self.id },
-        )
-        .map(|_| ())
+            Audit::record(
+                actor,
+                audit::Action::FileDelete,
+                audit::snapshot(self),
+                None,
+                conn,
+            )
+        })
     }
 
-    pub fn extract_from_id(id: i32, conn: &Connection) -> Result<Self, serv_prelude::ServiceError> {
-        Self::find_by_id(id, conn)?.ok_or_else(|| {
-            serv_prelude::ServiceError::not_found(
-                "FILE_NOT_FOUND",
-                "Specified file_id cannot be found",
-            )
+    pub fn unlink_all_tags(&self, actor: &str, conn: &Connection) -> SqlResult<()> {
+        atomically(conn, || {
+            let links =
+                relationships::Link::find_where("ft.`file_id`=?1", params! { self.id }, conn)?;
+
+            conn.execute(
+                "DELETE FROM `file_tags` WHERE `file_id`=?1",
+                params! { self.id },
+            )?;
+
+            relationships::Link::record_all(&links, audit::Action::FileTagDelete, actor, conn)
         })
     }
 
-    pub fn extract_id_exists(id: i32, conn: &Connection) -> Result<(), serv_prelude::ServiceError> {
+    pub fn extract_from_id(id: i32, conn: &Connection) -> library::Result<Self> {
+        Self::find_by_id(id, conn)?.ok_or(library::Error::FileNotFound)
+    }
+
+    pub fn extract_id_exists(id: i32, conn: &Connection) -> library::Result<()> {
         if !Self::id_exists(id, conn)? {
-            Err(serv_prelude::ServiceError::not_found(
-                "FILE_NOT_FOUND",
-                "Specified file_id cannot be found",
-            ))
+            Err(library::Error::FileNotFound)
         } else {
             Ok(())
         }
@@ -110,19 +163,11 @@ impl File {
             .optional()
     }
 
-    pub fn extract_from_name<N>(
-        name: N,
-        conn: &Connection,
-    ) -> Result<Self, serv_prelude::ServiceError>
+    pub fn extract_from_name<N>(name: N, conn: &Connection) -> library::Result<Self>
     where
         N: ToSql,
     {
-        Self::find_by_name(name, conn)?.ok_or_else(|| {
-            serv_prelude::ServiceError::not_found(
-                "FILE_NOT_FOUND",
-                "Specified file cannot be found",
-            )
-        })
+        Self::find_by_name(name, conn)?.ok_or(library::Error::FileNotFound)
     }
 
     pub fn find_by_name<N>(name: N, conn: &Connection) -> SqlResult<Option<Self>>
@@ -141,73 +186,81 @@ impl File {
         exact: bool,
         conn: &Connection,
     ) -> SqlResult<Vec<Self>> {
-        let mut tags = tags.to_vec();
-        tags.sort();
-
-        let mut files = if exact {
-            let rss = relationships::FileTag::all_for_tags_ids(tags.iter(), &conn)?;
-
-            let mut files = BTreeMap::<i32, Vec<i32>>::new();
-
-            for relationships::FileTag { file_id, tag_id } in rss {
-                match files.get_mut(&file_id) {
-                    Some(v) => {
-                        v.push(tag_id);
-                    }
-                    None => {
-                        files.insert(file_id, {
-                            let mut v = Vec::with_capacity(tags.len());
-                            v.push(tag_id);
-                            v
-                        });
-                    }
-                }
-            }
-
-            let ids: Vec<_> = files
-                .into_iter()
-                .filter_map(|(file_id, list)| if list == tags { Some(file_id) } else { None })
-                .collect();
-
-            File::find_specific_amount_where_in_ids_on_page(&ids, amount, page, &conn)?
-        } else {
-            let tags_array = RuSqlArray::new(tags.iter().map(|x| RuSqlValue::from(*x)).collect());
+        Self::find_specific_amount_by_query_on_page(
+            &search::FileQuery {
+                tags,
+                exact,
+                text: None,
+                sort: search::Sort::default(),
+                access: Access::All,
+            },
+            amount,
+            page,
+            conn,
+        )
+    }
 
-            conn.prepare(
-                "SELECT DISTINCT `files`.* FROM `file_tags` INNER JOIN `files` ON `id`=`file_id` WHERE `file_tags`.`tag_id` IN rarray(?) ORDER BY `id` DESC LIMIT ? OFFSET ?",
+    pub fn find_specific_amount_by_query_on_page(
+        query: &search::FileQuery,
+        amount: u32,
+        page: u32,
+        conn: &Connection,
+    ) -> SqlResult<Vec<Self>> {
+        let (ids, mut params) = query.files_ids();
+        params.push((":limit", Box::new(amount)));
+        params.push((":offset", Box::new(amount * page)));
+
+        let mut files = conn
+            .prepare(
+                &[
+                    "SELECT * FROM `files` WHERE `id` IN (",
+                    &ids,
+                    ") ORDER BY ",
+                    search::order_by(query.sort),
+                    " LIMIT :limit OFFSET :offset",
+                ]
+                .concat(),
             )?
-            .query_map(params!{&tags_array, amount, amount * page}, FromRow::from_row)?
-            .collect::<SqlResult<Vec<Self>>>()?
-        };
+            .query_map_named(&search::borrow_params(&params), FromRow::from_row)?
+            .collect::<SqlResult<Vec<Self>>>()?;
 
-        if files.is_empty() {
-            Ok(files)
-        } else {
-            let relationships =
-                relationships::FileTag::all_for_files_ids(files.iter().map(|f| f.id), &conn)?;
-            let tags = Tag::find_all_where_in_ids(
-                &relationships.iter().map(|t| t.tag_id).collect::<Box<[_]>>(),
-                &conn,
-            )?;
+        Self::load_tags(&mut files, query.access, conn)?;
 
-            let tags_map = tags
-                .iter()
-                .map(|t| (t.id, t))
-                .collect::<BTreeMap<i32, &Tag>>();
-            let mut files_map = files
-                .iter_mut()
-                .map(|f| (f.id, f))
-                .collect::<BTreeMap<i32, &mut File>>(); // FIXME: mut ???
+        Ok(files)
+    }
 
-            for relationships::FileTag { tag_id, file_id } in relationships {
-                let file = files_map.get_mut(&file_id).unwrap(); // FIXME: get_mut ???
-                let tag = tags_map.get(&tag_id).unwrap();
+    /// Fills `tags` of every file with tags visible with `access`.
+    pub fn load_tags(files: &mut [Self], access: Access, conn: &Connection) -> SqlResult<()> {
+        if files.is_empty() {
+            return Ok(());
+        }
 
+        let relationships =
+            relationships::FileTag::all_for_files_ids(files.iter().map(|f| f.id), &conn)?;
+        let tags = Tag::find_all_where_in_ids(
+            &relationships.iter().map(|t| t.tag_id).collect::<Box<[_]>>(),
+            &conn,
+        )?;
+
+        let tags_map = tags
+            .iter()
+            .map(|t| (t.id, t))
+            .collect::<BTreeMap<i32, &Tag>>();
+        let mut files_map = files
+            .iter_mut()
+            .map(|f| (f.id, f))
+            .collect::<BTreeMap<i32, &mut File>>(); // FIXME: mut ???
+
+        for relationships::FileTag { tag_id, file_id } in relationships {
+            let file = files_map.get_mut(&file_id).unwrap(); // FIXME: get_mut ???
+            let tag = tags_map.get(&tag_id).unwrap();
+
+            if access.can_see(tag.owner_id, tag.shared) {
                 file.tags.push(tag.to_owned().clone());
             }
-
-            Ok(files)
         }
+
+        Ok(())
     }
 
     pub fn name_exists<P>(name: P, conn: &Connection) -> SqlResult<bool>
@@ -233,14 +286,3 @@ impl File {
         Ok(())
     }
 }
-
-fn serialize_tags_vec<S>(src: &[Tag], serializer: S) -> Result<S::Ok, S::Error>
-where
-    S: Serializer,
-{
-    let mut seq = serializer.serialize_seq(Some(src.len()))?;
-    for e in src {
-        seq.serialize_element(&e.name)?;
-    }
-    seq.end()
-}
@@ -1,19 +1,52 @@
+pub mod audit;
+mod collection;
 mod file;
 pub mod relationships;
+mod saved_search;
+pub mod search;
+pub mod stats;
 mod tag;
+mod token;
+pub mod trash;
+mod user;
+pub mod webhook;
 
-// FIXME: remake structure
-mod serv_prelude {
-    pub use crate::serv::{routes::Result, ServiceError};
-    pub use actix_web::Responder;
-}
-
-use crate::{Connection, FromRow, SqlResult};
+use crate::{library, Connection, FromRow, SqlResult};
 use chrono::NaiveDateTime;
 use rusqlite::{
     params, types::Value as RuSqlValue, vtab::array::Array as RuSqlArray, OptionalExtension, ToSql,
 };
 use tagz_cg_from_row::FromRow;
 
+pub use audit::Audit;
+pub use collection::Collection;
 pub use file::File;
+pub use saved_search::SavedSearch;
 pub use tag::Tag;
+pub use token::Token;
+pub use trash::Trash;
+pub use user::{Access, Permission, Scope, User};
+pub use webhook::Webhook;
+
+/// Runs `f` in a savepoint, so a change and its audit rows are written
+/// together or not at all. Unlike [`Connection::transaction`] it works on a
+/// shared connection and nests in an outer transaction.
+pub fn atomically<T, F>(conn: &Connection, f: F) -> SqlResult<T>
+where
+    F: FnOnce() -> SqlResult<T>,
+{
+    conn.execute_batch("SAVEPOINT `atomically`")?;
+
+    match f() {
+        Ok(value) => {
+            conn.execute_batch("RELEASE `atomically`")?;
+
+            Ok(value)
+        }
+        Err(err) => {
+            conn.execute_batch("ROLLBACK TO `atomically`; RELEASE `atomically`")?;
+
+            Err(err)
+        }
+    }
+}
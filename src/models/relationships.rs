@@ -7,15 +7,79 @@ pub fn file_id_and_tag_id_exists(file_id: i32, tag_id: i32, conn: &Connection) -
         .map(|x: Option<i32>| x.is_some())
 }
 
+/// Returns whether relation existed.
 pub fn delete_between_file_id_and_tag_id(
     file_id: i32,
     tag_id: i32,
+    actor: &str,
     conn: &Connection,
 ) -> SqlResult<bool> {
-    conn.prepare("DELETE FROM `file_tags` WHERE `tag_id`=? AND `file_id`=?")?
-        .query_row(params! {file_id, tag_id}, |row| row.get(0))
-        .optional()
-        .map(|x: Option<i32>| x.is_some())
+    atomically(conn, || {
+        let links = Link::find_where(
+            "ft.`file_id`=?1 AND ft.`tag_id`=?2",
+            params! {file_id, tag_id},
+            conn,
+        )?;
+
+        conn.execute(
+            "DELETE FROM `file_tags` WHERE `file_id`=?1 AND `tag_id`=?2",
+            params! {file_id, tag_id},
+        )?;
+
+        Link::record_all(&links, audit::Action::FileTagDelete, actor, conn)?;
+
+        Ok(!links.is_empty())
+    })
+}
+
+/// Relation along with names and ownership of both sides, snapshot of it goes
+/// to audit, where it decides who sees the entry after either side is gone.
+#[derive(FromRow, serde::Serialize)]
+pub struct Link {
+    pub file_id: i32,
+    pub file: String,
+    pub file_owner_id: Option<i32>,
+    pub file_shared: bool,
+    pub tag_id: i32,
+    pub tag: String,
+    pub tag_owner_id: Option<i32>,
+    pub tag_shared: bool,
+}
+
+impl Link {
+    pub fn find_where(
+        condition: &str,
+        params: &[&dyn ToSql],
+        conn: &Connection,
+    ) -> SqlResult<Vec<Self>> {
+        conn.prepare(
+            &[
+                "SELECT ft.`file_id` AS file_id, f.`name` AS file, f.`owner_id` AS file_owner_id, f.`shared` AS file_shared, ft.`tag_id` AS tag_id, t.`name` AS tag, t.`owner_id` AS tag_owner_id, t.`shared` AS tag_shared FROM `file_tags` ft JOIN `files` f ON f.`id`=ft.`file_id` JOIN `tags` t ON t.`id`=ft.`tag_id` WHERE ",
+                condition,
+            ]
+            .concat(),
+        )?
+        .query_map(params, FromRow::from_row)?
+        .collect()
+    }
+
+    pub fn record_all(
+        links: &[Self],
+        action: audit::Action,
+        actor: &str,
+        conn: &Connection,
+    ) -> SqlResult<()> {
+        for link in links {
+            let snapshot = audit::snapshot(link);
+
+            match action {
+                audit::Action::FileTagCreate => Audit::record(actor, action, None, snapshot, conn)?,
+                _ => Audit::record(actor, action, snapshot, None, conn)?,
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(FromRow)]
@@ -25,12 +89,24 @@ pub struct FileTag {
 }
 
 impl FileTag {
-    pub fn create(file_id: i32, tag_id: i32, conn: &Connection) -> SqlResult<()> {
-        conn.execute(
-            "INSERT INTO `file_tags` (`file_id`, `tag_id`) VALUES (?, ?)",
-            params! {file_id, tag_id},
-        )
-        .map(|_| ())
+    pub fn create(file_id: i32, tag_id: i32, actor: &str, conn: &Connection) -> SqlResult<()> {
+        atomically(conn, || {
+            conn.execute(
+                "INSERT INTO `file_tags` (`file_id`, `tag_id`) VALUES (?, ?)",
+                params! {file_id, tag_id},
+            )?;
+
+            Link::record_all(
+                &Link::find_where(
+                    "ft.`file_id`=?1 AND ft.`tag_id`=?2",
+                    params! {file_id, tag_id},
+                    conn,
+                )?,
+                audit::Action::FileTagCreate,
+                actor,
+                conn,
+            )
+        })
     }
 
     pub fn all_for_tags_ids<I, S>(tags: I, conn: &Connection) -> SqlResult<Vec<Self>>
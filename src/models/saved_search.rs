@@ -0,0 +1,101 @@
+use super::*;
+use search::Sort;
+
+#[derive(Debug, FromRow, serde::Serialize)]
+pub struct SavedSearch {
+    #[serde(skip)]
+    pub id: i32,
+    pub name: String,
+    pub tags: String,
+    pub query: Option<String>,
+    pub sort: Sort,
+    pub exact: bool,
+    pub owner_id: Option<i32>,
+    pub shared: bool,
+    pub updated_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
+
+impl SavedSearch {
+    pub fn create<N, T, Q>(
+        name: N,
+        tags: T,
+        query: Q,
+        sort: Sort,
+        exact: bool,
+        owner_id: Option<i32>,
+        shared: bool,
+        conn: &Connection,
+    ) -> SqlResult<Self>
+    where
+        N: ToSql,
+        T: ToSql,
+        Q: ToSql,
+    {
+        conn.execute(
+            "INSERT INTO `searches` (name, tags, query, sort, exact, owner_id, shared) VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params! {name, tags, query, sort, exact, owner_id, shared},
+        )?;
+
+        tagz_cg_serv::last_inserted!(&conn, "searches")
+    }
+
+    pub fn update(&mut self, conn: &Connection) -> SqlResult<()> {
+        conn.execute(
+            "UPDATE `searches` SET name=?2, tags=?3, query=?4, sort=?5, exact=?6, shared=?7, updated_at=CURRENT_TIMESTAMP WHERE `id`=?1",
+            params! {self.id, self.name, self.tags, self.query, self.sort, self.exact, self.shared},
+        )?;
+
+        self.updated_at = conn.query_row(
+            "SELECT updated_at FROM `searches` WHERE `id`=?1",
+            params! {self.id},
+            |row| row.get(0),
+        )?;
+
+        Ok(())
+    }
+
+    pub fn delete(&self, conn: &Connection) -> SqlResult<()> {
+        conn.execute("DELETE FROM `searches` WHERE `id`=?1", params! { self.id })
+            .map(|_| ())
+    }
+
+    pub fn extract_from_name<N>(name: N, conn: &Connection) -> library::Result<Self>
+    where
+        N: ToSql,
+    {
+        Self::find_by_name(name, conn)?.ok_or(library::Error::SearchNotFound)
+    }
+
+    pub fn find_by_name<N>(name: N, conn: &Connection) -> SqlResult<Option<Self>>
+    where
+        N: ToSql,
+    {
+        conn.prepare("SELECT * FROM `searches` WHERE `name`=?1 LIMIT 1")?
+            .query_row(params! {name}, FromRow::from_row)
+            .optional()
+    }
+
+    pub fn name_exists<N>(name: N, conn: &Connection) -> SqlResult<bool>
+    where
+        N: ToSql,
+    {
+        conn.prepare("SELECT 1 FROM `searches` WHERE `name`=?1 LIMIT 1")?
+            .query_row(params! {name}, |row| row.get(0))
+            .optional()
+            .map(|x: Option<i32>| x.is_some())
+    }
+
+    pub fn all(access: Access, conn: &Connection) -> SqlResult<Vec<Self>> {
+        conn.prepare(
+            &[
+                "SELECT * FROM `searches` WHERE ",
+                &access.condition("searches"),
+                " ORDER BY `name` ASC",
+            ]
+            .concat(),
+        )?
+        .query_map(params! {}, Self::from_row)?
+        .collect()
+    }
+}
@@ -0,0 +1,141 @@
+use super::*;
+
+pub fn register_functions(conn: &Connection) -> SqlResult<()> {
+    conn.create_scalar_function("tagz_tokens", 1, true, |ctx| {
+        Ok(ctx.get::<Option<String>>(0)?.map(|text| tokenize(&text)))
+    })
+}
+
+/// Splits `text` into lowercase words on anything but letters and digits,
+/// then on camelCase and letter/digit boundaries.
+pub fn words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+
+    for segment in text.split(|c: char| !c.is_alphanumeric()) {
+        let chars = segment.chars().collect::<Vec<_>>();
+        let mut start = 0;
+
+        for idx in 1..chars.len() {
+            let (prev, cur) = (chars[idx - 1], chars[idx]);
+            let next_lower = chars.get(idx + 1).map_or(false, |c| c.is_lowercase());
+
+            let boundary = (prev.is_lowercase() && cur.is_uppercase())
+                || (prev.is_uppercase() && cur.is_uppercase() && next_lower)
+                || (prev.is_alphabetic() && cur.is_numeric())
+                || (prev.is_numeric() && cur.is_alphabetic());
+
+            if boundary {
+                words.push(chars[start..idx].iter().collect::<String>().to_lowercase());
+                start = idx;
+            }
+        }
+
+        if start < chars.len() {
+            words.push(chars[start..].iter().collect::<String>().to_lowercase());
+        }
+    }
+
+    words
+}
+
+/// Text stored in the fts5 index: every word plus every unsplit segment, so both
+/// `photo` and `myphotoalbum` find `myPhotoAlbum.jpg`.
+pub fn tokenize(text: &str) -> String {
+    let mut tokens = words(text);
+
+    for segment in text.split(|c: char| !c.is_alphanumeric()) {
+        let segment = segment.to_lowercase();
+
+        if !segment.is_empty() && !tokens.contains(&segment) {
+            tokens.push(segment);
+        }
+    }
+
+    tokens.join(" ")
+}
+
+/// Builds fts5 query matching every word of `query` as a prefix,
+/// `None` if there is nothing to search for.
+pub fn match_expression(query: &str) -> Option<String> {
+    let words = words(query);
+
+    if words.is_empty() {
+        None
+    } else {
+        Some(
+            words
+                .iter()
+                .map(|word| ["\"", word, "\"*"].concat())
+                .collect::<Vec<_>>()
+                .join(" AND "),
+        )
+    }
+}
+
+pub type NamedParams = Vec<(&'static str, Box<dyn ToSql>)>;
+
+pub use tagz_types::Sort;
+
+/// `ORDER BY` clause of files listed in `sort` order.
+pub fn order_by(sort: Sort) -> &'static str {
+    match sort {
+        Sort::Newest => "`id` DESC",
+        Sort::Oldest => "`id` ASC",
+        Sort::Name => "`name` ASC",
+        Sort::NameDesc => "`name` DESC",
+        Sort::Updated => "`updated_at` DESC, `id` DESC",
+    }
+}
+
+pub struct FileQuery<'a> {
+    pub tags: &'a [i32],
+    pub exact: bool,
+    /// fts5 expression, see [`match_expression`]
+    pub text: Option<String>,
+    pub sort: Sort,
+    pub access: Access,
+}
+
+impl FileQuery<'_> {
+    /// `SELECT` of ids of matching files along with its named parameters.
+    pub fn files_ids(&self) -> (String, NamedParams) {
+        let mut sql = [
+            "SELECT `id` FROM `files` WHERE ",
+            &self.access.condition("files"),
+        ]
+        .concat();
+        let mut params: NamedParams = Vec::with_capacity(3);
+
+        if !self.tags.is_empty() {
+            sql.push_str(" AND `id` IN (SELECT `file_id` FROM `file_tags` WHERE `tag_id` IN rarray(:tags) GROUP BY `file_id` HAVING COUNT(*) >= :required)");
+            params.push((":tags", Box::new(self.tags_array())));
+            params.push((
+                ":required",
+                Box::new(if self.exact {
+                    self.tags.len() as i64
+                } else {
+                    1
+                }),
+            ));
+        }
+
+        if let Some(text) = &self.text {
+            // private tags of other users must not match, or their names leak
+            sql.push_str(" AND `id` IN (SELECT `rowid` FROM `files_search` WHERE `files_search` MATCH :text UNION SELECT `file_id` FROM `file_tags` WHERE `tag_id` IN (SELECT `tags`.`id` FROM `tags_search` INNER JOIN `tags` ON `tags`.`id`=`tags_search`.`rowid` WHERE `tags_search` MATCH :text AND ");
+            sql.push_str(&self.access.condition("tags"));
+            sql.push_str("))");
+            params.push((":text", Box::new(text.clone())));
+        }
+
+        (sql, params)
+    }
+
+    pub fn tags_array(&self) -> RuSqlArray {
+        RuSqlArray::new(self.tags.iter().map(|x| RuSqlValue::from(*x)).collect())
+    }
+}
+
+#[inline]
+pub fn borrow_params(params: &NamedParams) -> Vec<(&str, &dyn ToSql)> {
+    params.iter().map(|(k, v)| (*k, v.as_ref())).collect()
+}
@@ -0,0 +1,113 @@
+use super::*;
+
+#[derive(Clone, Debug, FromRow, serde::Serialize)]
+#[serde(into = "tagz_types::RelatedTag")]
+pub struct RelatedTag {
+    pub id: i32,
+    pub name: String,
+    pub score: f64,
+    pub occurrences: i64,
+}
+
+impl From<RelatedTag> for tagz_types::RelatedTag {
+    fn from(tag: RelatedTag) -> Self {
+        Self {
+            name: tag.name,
+            score: tag.score,
+            occurrences: tag.occurrences,
+        }
+    }
+}
+
+impl RelatedTag {
+    /// Ranks tags by the average share of files carrying one of `tags` that also carry them.
+    pub fn find_for_tags_ids(
+        tags: &[i32],
+        limit: u32,
+        access: Access,
+        conn: &Connection,
+    ) -> SqlResult<Vec<Self>> {
+        if tags.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let tags_array = RuSqlArray::new(tags.iter().map(|x| RuSqlValue::from(*x)).collect());
+
+        conn.prepare(&[
+            "SELECT `tags`.`id`, `tags`.`name`, SUM(1.0 / `base`.`total`) / ?2 AS `score`, COUNT(DISTINCT `other`.`file_id`) AS `occurrences`
+            FROM `file_tags` AS `given`
+            INNER JOIN (SELECT `tag_id`, COUNT(*) AS `total` FROM `file_tags` WHERE `tag_id` IN rarray(?1) GROUP BY `tag_id`) AS `base` ON `base`.`tag_id`=`given`.`tag_id`
+            INNER JOIN `file_tags` AS `other` ON `other`.`file_id`=`given`.`file_id` AND `other`.`tag_id` NOT IN rarray(?1)
+            INNER JOIN `tags` ON `tags`.`id`=`other`.`tag_id`
+            WHERE ",
+            &access.condition("tags"),
+            "
+            GROUP BY `other`.`tag_id`
+            ORDER BY `score` DESC, `occurrences` DESC, `tags`.`name` ASC
+            LIMIT ?3",
+        ].concat())?
+        .query_map(params! { &tags_array, tags.len() as i64, limit }, FromRow::from_row)?
+        .collect()
+    }
+
+    pub fn find_for_file_id(
+        file_id: i32,
+        limit: u32,
+        access: Access,
+        conn: &Connection,
+    ) -> SqlResult<Vec<Self>> {
+        let tags = relationships::FileTag::all_for_files_ids(std::iter::once(file_id), conn)?
+            .into_iter()
+            .map(|rel| rel.tag_id)
+            .collect::<Box<[_]>>();
+
+        Self::find_for_tags_ids(&tags, limit, access, conn)
+    }
+}
+
+#[derive(Clone, Debug, FromRow, serde::Serialize)]
+#[serde(into = "tagz_types::Facet")]
+pub struct Facet {
+    pub name: String,
+    pub count: i64,
+}
+
+impl From<Facet> for tagz_types::Facet {
+    fn from(facet: Facet) -> Self {
+        Self {
+            name: facet.name,
+            count: facet.count,
+        }
+    }
+}
+
+impl Facet {
+    /// Counts other tags across every file matched by `query`, not only the requested page.
+    pub fn find_for_query(query: &search::FileQuery, conn: &Connection) -> SqlResult<Vec<Self>> {
+        let (ids, mut params) = query.files_ids();
+        params.push((":excluded", Box::new(query.tags_array())));
+
+        conn.prepare(
+            &[
+                "SELECT `tags`.`name`, COUNT(*) AS `count` FROM `file_tags` INNER JOIN `tags` ON `tags`.`id`=`file_tags`.`tag_id` WHERE `file_tags`.`file_id` IN (",
+                &ids,
+                ") AND `file_tags`.`tag_id` NOT IN rarray(:excluded) AND ",
+                &query.access.condition("tags"),
+                " GROUP BY `file_tags`.`tag_id` ORDER BY `count` DESC, `tags`.`name` ASC",
+            ]
+            .concat(),
+        )?
+        .query_map_named(&search::borrow_params(&params), FromRow::from_row)?
+        .collect()
+    }
+}
+
+pub fn count_files_by_query(query: &search::FileQuery, conn: &Connection) -> SqlResult<i64> {
+    let (ids, params) = query.files_ids();
+
+    conn.query_row_named(
+        &["SELECT COUNT(*) FROM (", &ids, ")"].concat(),
+        &search::borrow_params(&params),
+        |row| row.get(0),
+    )
+}
@@ -1,33 +1,132 @@
 use super::*;
 
 #[derive(Clone, Debug, FromRow, serde::Serialize)]
+#[serde(into = "tagz_types::Tag")]
 pub struct Tag {
     pub id: i32,
     pub name: String,
+    pub description: Option<String>,
+    pub owner_id: Option<i32>,
+    pub shared: bool,
     pub created_at: NaiveDateTime,
 }
 
+impl From<Tag> for tagz_types::Tag {
+    fn from(tag: Tag) -> Self {
+        Self {
+            id: tag.id,
+            name: tag.name,
+            description: tag.description,
+            owner_id: tag.owner_id,
+            shared: tag.shared,
+            created_at: tag.created_at,
+        }
+    }
+}
+
 impl Tag {
-    pub fn create<N>(name: N, conn: &Connection) -> SqlResult<Self>
+    pub fn create<N>(name: N, actor: &str, conn: &Connection) -> SqlResult<Self>
     where
         N: ToSql,
     {
-        conn.execute("INSERT INTO `tags` (name) VALUES(?1)", params! {name})?;
+        atomically(conn, || {
+            conn.execute("INSERT INTO `tags` (name) VALUES(?1)", params! {name})?;
 
-        tagz_cg_serv::last_inserted!(&conn, "tags")
+            let inst = tagz_cg_serv::last_inserted!(&conn, "tags")?;
+            Audit::record(
+                actor,
+                audit::Action::TagCreate,
+                None,
+                audit::snapshot(&inst),
+                conn,
+            )?;
+
+            Ok(inst)
+        })
     }
 
-    pub fn unlink_all_files(&self, conn: &Connection) -> SqlResult<()> {
-        conn.execute(
-            "DELETE FROM `file_tags` WHERE `tag_id`=?1",
-            params! { self.id },
-        )
-        .map(|_| ())
+    pub fn create_with_description<N, D>(
+        name: N,
+        description: D,
+        owner_id: Option<i32>,
+        shared: bool,
+        actor: &str,
+        conn: &Connection,
+    ) -> SqlResult<Self>
+    where
+        N: ToSql,
+        D: ToSql,
+    {
+        atomically(conn, || {
+            conn.execute(
+                "INSERT INTO `tags` (name, description, owner_id, shared) VALUES(?1, ?2, ?3, ?4)",
+                params! {name, description, owner_id, shared},
+            )?;
+
+            let inst = tagz_cg_serv::last_inserted!(&conn, "tags")?;
+            Audit::record(
+                actor,
+                audit::Action::TagCreate,
+                None,
+                audit::snapshot(&inst),
+                conn,
+            )?;
+
+            Ok(inst)
+        })
     }
 
-    pub fn delete(&self, conn: &Connection) -> SqlResult<()> {
-        conn.execute("DELETE FROM `tags` WHERE `id`=?1", params! { self.id })
-            .map(|_| ())
+    pub fn unlink_all_files(&self, actor: &str, conn: &Connection) -> SqlResult<()> {
+        atomically(conn, || {
+            let links =
+                relationships::Link::find_where("ft.`tag_id`=?1", params! { self.id }, conn)?;
+
+            conn.execute(
+                "DELETE FROM `file_tags` WHERE `tag_id`=?1",
+                params! { self.id },
+            )?;
+
+            relationships::Link::record_all(&links, audit::Action::FileTagDelete, actor, conn)
+        })
+    }
+
+    pub fn rename(&mut self, name: &str, actor: &str, conn: &Connection) -> SqlResult<()> {
+        let before = audit::snapshot(self);
+        let mut after = self.clone();
+        after.name = name.to_owned();
+
+        atomically(conn, || {
+            conn.execute(
+                "UPDATE `tags` SET name=?2 WHERE `id`=?1",
+                params! {self.id, name},
+            )?;
+
+            Audit::record(
+                actor,
+                audit::Action::TagUpdate,
+                before,
+                audit::snapshot(&after),
+                conn,
+            )
+        })?;
+
+        *self = after;
+
+        Ok(())
+    }
+
+    pub fn delete(&self, actor: &str, conn: &Connection) -> SqlResult<()> {
+        atomically(conn, || {
+            conn.execute("DELETE FROM `tags` WHERE `id`=?1", params! { self.id })?;
+
+            Audit::record(
+                actor,
+                audit::Action::TagDelete,
+                audit::snapshot(self),
+                None,
+                conn,
+            )
+        })
     }
 
     pub fn has_related_files(&self, conn: &Connection) -> SqlResult<bool> {
@@ -37,16 +136,17 @@ impl Tag {
             .map(|x: Option<i32>| x.is_some())
     }
 
-    pub fn extract_from_name<N>(
-        name: N,
-        conn: &Connection,
-    ) -> Result<Self, serv_prelude::ServiceError>
+    pub fn extract_from_name<N>(name: N, conn: &Connection) -> library::Result<Self>
     where
         N: ToSql,
     {
-        Self::find_by_name(name, conn)?.ok_or_else(|| {
-            serv_prelude::ServiceError::not_found("TAG_NOT_FOUND", "Specified tag cannot be found")
-        })
+        Self::find_by_name(name, conn)?.ok_or(library::Error::TagNotFound)
+    }
+
+    pub fn find_by_id(id: i32, conn: &Connection) -> SqlResult<Option<Self>> {
+        conn.prepare("SELECT * FROM `tags` WHERE `id`=?1 LIMIT 1")?
+            .query_row(params! {id}, FromRow::from_row)
+            .optional()
     }
 
     pub fn find_by_name<N>(name: N, conn: &Connection) -> SqlResult<Option<Self>>
@@ -70,6 +170,7 @@ impl Tag {
 
     pub fn find_all_where_in_names<S: ToString>(
         names: &[S],
+        access: Access,
         conn: &Connection,
     ) -> SqlResult<Vec<Self>> {
         let names = RuSqlArray::new(
@@ -79,9 +180,15 @@ impl Tag {
                 .collect(),
         );
 
-        conn.prepare("SELECT * FROM `tags` WHERE `name` IN rarray(?)")?
-            .query_map(&[&names], FromRow::from_row)?
-            .collect()
+        conn.prepare(
+            &[
+                "SELECT * FROM `tags` WHERE `name` IN rarray(?) AND ",
+                &access.condition("tags"),
+            ]
+            .concat(),
+        )?
+        .query_map(&[&names], FromRow::from_row)?
+        .collect()
     }
 
     pub fn find_all_where_in_ids(ids: &[i32], conn: &Connection) -> SqlResult<Vec<Self>> {
@@ -94,14 +201,28 @@ impl Tag {
 
     pub fn find_related_to_file(file: i32, conn: &Connection) -> SqlResult<Vec<Self>> {
         conn.prepare(
-            "SELECT `tags`.* FROM `file_tags` WHERE `file_id`=?1 INNER JOIN `tags` ON `id`=`tag_id`",
+            "SELECT `tags`.* FROM `file_tags` INNER JOIN `tags` ON `id`=`tag_id` WHERE `file_id`=?1",
         )?
         .query_map(params! {file}, Self::from_row)?
         .collect()
     }
 
-    pub fn all(conn: &Connection) -> SqlResult<Vec<Self>> {
-        conn.prepare("SELECT * FROM `tags`")?
+    /// `text` is fts5 expression, see [`search::match_expression`]
+    pub fn search(text: &str, access: Access, conn: &Connection) -> SqlResult<Vec<Self>> {
+        conn.prepare(
+            &[
+                "SELECT `tags`.* FROM `tags_search` INNER JOIN `tags` ON `tags`.`id`=`tags_search`.`rowid` WHERE `tags_search` MATCH ?1 AND ",
+                &access.condition("tags"),
+                " ORDER BY `rank`",
+            ]
+            .concat(),
+        )?
+        .query_map(params! {text}, Self::from_row)?
+        .collect()
+    }
+
+    pub fn all(access: Access, conn: &Connection) -> SqlResult<Vec<Self>> {
+        conn.prepare(&["SELECT * FROM `tags` WHERE ", &access.condition("tags")].concat())?
             .query_map(params! {}, Self::from_row)?
             .collect()
     }
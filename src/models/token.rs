@@ -0,0 +1,94 @@
+use super::*;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+
+#[derive(Debug, FromRow, serde::Serialize)]
+pub struct Token {
+    #[serde(skip)]
+    pub id: i32,
+    pub name: String,
+
+    #[serde(skip)]
+    pub hash: String,
+    pub user_id: Option<i32>,
+    pub scope: Scope,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+/// Only the sha256 of a secret is stored, so it is shown once on creation.
+pub fn hash_secret(secret: &str) -> String {
+    format!("{:x}", Sha256::digest(secret.as_bytes()))
+}
+
+pub fn generate_secret() -> String {
+    rand::thread_rng()
+        .gen::<[u8; 32]>()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+impl Token {
+    /// Returns created token along with its plain secret,
+    /// token without user is limited by its scope only.
+    pub fn create<N>(
+        name: N,
+        user_id: Option<i32>,
+        scope: Scope,
+        conn: &Connection,
+    ) -> SqlResult<(Self, String)>
+    where
+        N: ToSql,
+    {
+        let secret = generate_secret();
+
+        conn.execute(
+            "INSERT INTO `tokens` (name, hash, user_id, scope) VALUES(?1, ?2, ?3, ?4)",
+            params! {name, hash_secret(&secret), user_id, scope},
+        )?;
+
+        Ok((tagz_cg_serv::last_inserted!(&conn, "tokens")?, secret))
+    }
+
+    pub fn delete(&self, conn: &Connection) -> SqlResult<()> {
+        conn.execute("DELETE FROM `tokens` WHERE `id`=?1", params! { self.id })
+            .map(|_| ())
+    }
+
+    pub fn touch(&self, conn: &Connection) -> SqlResult<()> {
+        conn.execute(
+            "UPDATE `tokens` SET last_used_at=CURRENT_TIMESTAMP WHERE `id`=?1",
+            params! { self.id },
+        )
+        .map(|_| ())
+    }
+
+    pub fn find_by_secret(secret: &str, conn: &Connection) -> SqlResult<Option<Self>> {
+        conn.prepare("SELECT * FROM `tokens` WHERE `hash`=?1 LIMIT 1")?
+            .query_row(params! {hash_secret(secret)}, FromRow::from_row)
+            .optional()
+    }
+
+    pub fn find_by_name<N>(name: N, conn: &Connection) -> SqlResult<Option<Self>>
+    where
+        N: ToSql,
+    {
+        conn.prepare("SELECT * FROM `tokens` WHERE `name`=?1 LIMIT 1")?
+            .query_row(params! {name}, FromRow::from_row)
+            .optional()
+    }
+
+    pub fn any_exists(conn: &Connection) -> SqlResult<bool> {
+        conn.prepare("SELECT 1 FROM `tokens` LIMIT 1")?
+            .query_row(params! {}, |row| row.get(0))
+            .optional()
+            .map(|x: Option<i32>| x.is_some())
+    }
+
+    pub fn all(conn: &Connection) -> SqlResult<Vec<Self>> {
+        conn.prepare("SELECT * FROM `tokens` ORDER BY `name` ASC")?
+            .query_map(params! {}, Self::from_row)?
+            .collect()
+    }
+}
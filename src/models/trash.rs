@@ -0,0 +1,303 @@
+use super::*;
+use audit::{snapshot, Snapshot};
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, Type, ValueRef};
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    File,
+    Tag,
+}
+
+impl Kind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Kind::File => "file",
+            Kind::Tag => "tag",
+        }
+    }
+}
+
+impl FromStr for Kind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "file" => Kind::File,
+            "tag" => Kind::Tag,
+            _ => return Err(()),
+        })
+    }
+}
+
+impl ToSql for Kind {
+    fn to_sql(&self) -> SqlResult<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for Kind {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()
+            .and_then(|s| s.parse().map_err(|_| FromSqlError::InvalidType))
+    }
+}
+
+/// Deleted file or tag along with its relations, restorable until purged.
+#[derive(Debug, FromRow, serde::Serialize)]
+pub struct Trash {
+    pub id: i32,
+    pub kind: Kind,
+    pub name: String,
+    pub owner_id: Option<i32>,
+    pub shared: bool,
+    /// deleted row
+    pub entry: Snapshot,
+    /// [`FileRelations`] of a file or ids of files of a tag
+    pub relations: Snapshot,
+    pub actor: String,
+    pub deleted_at: NaiveDateTime,
+}
+
+/// Tags of a file and its places in collections.
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct FileRelations {
+    pub tags: Vec<i32>,
+    /// `(collection_id, position)`
+    pub collections: Vec<(i32, u32)>,
+}
+
+impl FileRelations {
+    /// Entries trashed before collections were kept list only ids of tags.
+    fn from_snapshot(snapshot: &Snapshot) -> SqlResult<Self> {
+        from_snapshot(snapshot).or_else(|_| {
+            Ok(Self {
+                tags: from_snapshot(snapshot)?,
+                collections: Vec::new(),
+            })
+        })
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct FileRow {
+    id: i32,
+    name: String,
+    owner_id: Option<i32>,
+    shared: bool,
+    updated_at: NaiveDateTime,
+    created_at: NaiveDateTime,
+}
+
+#[derive(serde::Deserialize)]
+struct TagRow {
+    id: i32,
+    name: String,
+    description: Option<String>,
+    owner_id: Option<i32>,
+    shared: bool,
+    created_at: NaiveDateTime,
+}
+
+fn from_snapshot<T>(snapshot: &Snapshot) -> SqlResult<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    serde_json::from_value(snapshot.0.clone())
+        .map_err(|err| rusqlite::Error::FromSqlConversionFailure(0, Type::Text, Box::new(err)))
+}
+
+macro_rules! insert {
+    ($conn:expr, $kind:expr, $name:expr, $owner_id:expr, $shared:expr, $entry:expr, $relations:expr, $actor:expr) => {
+        $conn.execute(
+            "INSERT INTO `trash` (kind, name, owner_id, shared, entry, relations, actor) VALUES(?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params! {
+                $kind, $name, $owner_id, $shared, $entry, $relations, $actor
+            },
+        )
+    };
+}
+
+impl Trash {
+    /// Moves file to trash, unlinking its tags and taking it out of collections.
+    pub fn put_file(file: &File, actor: &str, conn: &Connection) -> SqlResult<Self> {
+        atomically(conn, || {
+            let relations = FileRelations {
+                tags: conn
+                    .prepare("SELECT `tag_id` FROM `file_tags` WHERE `file_id`=?1")?
+                    .query_map(params! { file.id }, |row| row.get(0))?
+                    .collect::<SqlResult<Vec<i32>>>()?,
+                collections: Collection::memberships_of(file.id, conn)?,
+            };
+
+            insert!(
+                conn,
+                Kind::File,
+                file.name,
+                file.owner_id,
+                file.shared,
+                snapshot(file),
+                snapshot(&relations),
+                actor
+            )?;
+
+            let inst = tagz_cg_serv::last_inserted!(conn, "trash")?;
+
+            for (collection_id, _) in &relations.collections {
+                if let Some(collection) = Collection::find_by_id(*collection_id, conn)? {
+                    collection.remove_file(file.id, conn)?;
+                }
+            }
+
+            file.unlink_all_tags(actor, conn)?;
+            file.delete(actor, conn)?;
+
+            Ok(inst)
+        })
+    }
+
+    /// Moves tag to trash, unlinking its files.
+    pub fn put_tag(tag: &Tag, actor: &str, conn: &Connection) -> SqlResult<Self> {
+        atomically(conn, || {
+            let files = conn
+                .prepare("SELECT `file_id` FROM `file_tags` WHERE `tag_id`=?1")?
+                .query_map(params! { tag.id }, |row| row.get(0))?
+                .collect::<SqlResult<Vec<i32>>>()?;
+
+            insert!(
+                conn,
+                Kind::Tag,
+                tag.name,
+                tag.owner_id,
+                tag.shared,
+                snapshot(tag),
+                snapshot(&files),
+                actor
+            )?;
+
+            let inst = tagz_cg_serv::last_inserted!(conn, "trash")?;
+
+            tag.unlink_all_files(actor, conn)?;
+            tag.delete(actor, conn)?;
+
+            Ok(inst)
+        })
+    }
+
+    /// Inserts entry back with its former id, relations whose other side
+    /// was deleted meanwhile are skipped. Name must be free. A file goes back
+    /// to its positions in collections, as far as they are long enough.
+    pub fn restore(&self, actor: &str, conn: &Connection) -> SqlResult<()> {
+        atomically(conn, || {
+            let links = match self.kind {
+                Kind::File => {
+                    let row = from_snapshot::<FileRow>(&self.entry)?;
+                    let relations = FileRelations::from_snapshot(&self.relations)?;
+
+                    conn.execute(
+                        "INSERT INTO `files` (id, name, owner_id, shared, updated_at, created_at) VALUES(?1, ?2, ?3, ?4, ?5, ?6)",
+                        params! {row.id, row.name, row.owner_id, row.shared, row.updated_at, row.created_at},
+                    )?;
+
+                    for tag_id in relations.tags {
+                        conn.execute(
+                            "INSERT INTO `file_tags` (file_id, tag_id) SELECT ?1, ?2 WHERE EXISTS (SELECT 1 FROM `tags` WHERE `id`=?2)",
+                            params! {row.id, tag_id},
+                        )?;
+                    }
+
+                    for (collection_id, position) in relations.collections {
+                        if let Some(collection) = Collection::find_by_id(collection_id, conn)? {
+                            collection.insert_file(row.id, Some(position), conn)?;
+                        }
+                    }
+
+                    let mut file = conn.query_row(
+                        "SELECT * FROM `files` WHERE `id`=?1",
+                        params! { row.id },
+                        File::from_row,
+                    )?;
+                    file.update_tags(conn)?;
+                    Audit::record(
+                        actor,
+                        audit::Action::FileRestore,
+                        None,
+                        snapshot(&file),
+                        conn,
+                    )?;
+
+                    relationships::Link::find_where("ft.`file_id`=?1", params! { row.id }, conn)?
+                }
+                Kind::Tag => {
+                    let row = from_snapshot::<TagRow>(&self.entry)?;
+                    let related = from_snapshot::<Vec<i32>>(&self.relations)?;
+
+                    conn.execute(
+                        "INSERT INTO `tags` (id, name, description, owner_id, shared, created_at) VALUES(?1, ?2, ?3, ?4, ?5, ?6)",
+                        params! {row.id, row.name, row.description, row.owner_id, row.shared, row.created_at},
+                    )?;
+
+                    for file_id in related {
+                        conn.execute(
+                            "INSERT INTO `file_tags` (file_id, tag_id) SELECT ?1, ?2 WHERE EXISTS (SELECT 1 FROM `files` WHERE `id`=?1)",
+                            params! {file_id, row.id},
+                        )?;
+                    }
+
+                    let tag = conn.query_row(
+                        "SELECT * FROM `tags` WHERE `id`=?1",
+                        params! { row.id },
+                        Tag::from_row,
+                    )?;
+                    Audit::record(actor, audit::Action::TagRestore, None, snapshot(&tag), conn)?;
+
+                    relationships::Link::find_where("ft.`tag_id`=?1", params! { row.id }, conn)?
+                }
+            };
+
+            relationships::Link::record_all(&links, audit::Action::FileTagCreate, actor, conn)?;
+
+            conn.execute("DELETE FROM `trash` WHERE `id`=?1", params! { self.id })
+                .map(|_| ())
+        })
+    }
+
+    /// Returns amount of purged entries.
+    pub fn purge_older_than(days: u32, conn: &Connection) -> SqlResult<usize> {
+        conn.execute(
+            "DELETE FROM `trash` WHERE `deleted_at` < datetime('now', ?1)",
+            params! { format!("-{} days", days) },
+        )
+    }
+
+    pub fn extract_from_id(id: i32, conn: &Connection) -> library::Result<Self> {
+        Self::find_by_id(id, conn)?.ok_or(library::Error::TrashNotFound)
+    }
+
+    pub fn find_by_id(id: i32, conn: &Connection) -> SqlResult<Option<Self>> {
+        conn.prepare("SELECT * FROM `trash` WHERE `id`=?1 LIMIT 1")?
+            .query_row(params! {id}, FromRow::from_row)
+            .optional()
+    }
+
+    /// Recently deleted first.
+    pub fn find_specific_amount_on_page(
+        access: Access,
+        amount: u32,
+        page: u32,
+        conn: &Connection,
+    ) -> SqlResult<Vec<Self>> {
+        conn.prepare(
+            &[
+                "SELECT * FROM `trash` WHERE ",
+                &access.condition("trash"),
+                " ORDER BY `id` DESC LIMIT ?1 OFFSET ?2",
+            ]
+            .concat(),
+        )?
+        .query_map(params! { amount, amount * page }, FromRow::from_row)?
+        .collect()
+    }
+}
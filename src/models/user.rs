@@ -0,0 +1,210 @@
+use super::*;
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, ValueRef};
+use std::str::FromStr;
+
+/// Ordered, so each level includes the previous ones.
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    /// browse tags, files, searches and collections
+    Read,
+    /// create, tag and delete own entries
+    Tag,
+    /// everything, including entries owned by others
+    Admin,
+}
+
+impl Permission {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Permission::Read => "read",
+            Permission::Tag => "tag",
+            Permission::Admin => "admin",
+        }
+    }
+}
+
+impl FromStr for Permission {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "read" => Permission::Read,
+            "tag" => Permission::Tag,
+            "admin" => Permission::Admin,
+            _ => return Err(()),
+        })
+    }
+}
+
+/// What a token allows on top of its user role.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    Read,
+    Write,
+    Admin,
+}
+
+impl Scope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scope::Read => "read",
+            Scope::Write => "write",
+            Scope::Admin => "admin",
+        }
+    }
+
+    pub fn permission(self) -> Permission {
+        match self {
+            Scope::Read => Permission::Read,
+            Scope::Write => Permission::Tag,
+            Scope::Admin => Permission::Admin,
+        }
+    }
+}
+
+impl FromStr for Scope {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "read" => Scope::Read,
+            "write" => Scope::Write,
+            "admin" => Scope::Admin,
+            _ => return Err(()),
+        })
+    }
+}
+
+impl ToSql for Scope {
+    fn to_sql(&self) -> SqlResult<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for Scope {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()
+            .and_then(|s| s.parse().map_err(|_| FromSqlError::InvalidType))
+    }
+}
+
+impl ToSql for Permission {
+    fn to_sql(&self) -> SqlResult<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for Permission {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()
+            .and_then(|s| s.parse().map_err(|_| FromSqlError::InvalidType))
+    }
+}
+
+/// Which owned entries are visible: everything, or shared ones plus those of a user.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Access {
+    All,
+    User(i32),
+}
+
+impl Access {
+    /// SQL condition on `owner_id` and `shared` columns of `table`.
+    pub fn condition(self, table: &str) -> String {
+        match self {
+            Access::All => "1".to_owned(),
+            Access::User(id) => format!(
+                "(`{0}`.`shared`=1 OR `{0}`.`owner_id` IS NULL OR `{0}`.`owner_id`={1})",
+                table, id
+            ),
+        }
+    }
+
+    pub fn can_see(self, owner_id: Option<i32>, shared: bool) -> bool {
+        match self {
+            Access::All => true,
+            Access::User(id) => shared || owner_id.map_or(true, |owner| owner == id),
+        }
+    }
+}
+
+#[derive(Clone, Debug, FromRow, serde::Serialize)]
+pub struct User {
+    pub id: i32,
+    pub name: String,
+    pub role: Permission,
+    pub created_at: NaiveDateTime,
+}
+
+impl User {
+    pub fn create<N>(name: N, role: Permission, conn: &Connection) -> SqlResult<Self>
+    where
+        N: ToSql,
+    {
+        conn.execute(
+            "INSERT INTO `users` (name, role) VALUES(?1, ?2)",
+            params! {name, role},
+        )?;
+
+        tagz_cg_serv::last_inserted!(&conn, "users")
+    }
+
+    pub fn set_role(&mut self, role: Permission, conn: &Connection) -> SqlResult<()> {
+        conn.execute(
+            "UPDATE `users` SET role=?2 WHERE `id`=?1",
+            params! {self.id, role},
+        )?;
+
+        self.role = role;
+
+        Ok(())
+    }
+
+    /// Revokes user tokens and gives owned entries, trashed ones included, to
+    /// `heir`, so private ones stay private.
+    pub fn delete(&self, heir: &User, conn: &Connection) -> SqlResult<()> {
+        atomically(conn, || {
+            conn.execute(
+                "DELETE FROM `tokens` WHERE `user_id`=?1",
+                params! { self.id },
+            )?;
+
+            for table in &["files", "tags", "collections", "searches", "trash"] {
+                conn.execute(
+                    &format!("UPDATE `{}` SET owner_id=?2 WHERE `owner_id`=?1", table),
+                    params! { self.id, heir.id },
+                )?;
+            }
+
+            conn.execute("DELETE FROM `users` WHERE `id`=?1", params! { self.id })
+                .map(|_| ())
+        })
+    }
+
+    pub fn find_by_id(id: i32, conn: &Connection) -> SqlResult<Option<Self>> {
+        conn.prepare("SELECT * FROM `users` WHERE `id`=?1 LIMIT 1")?
+            .query_row(params! {id}, FromRow::from_row)
+            .optional()
+    }
+
+    pub fn find_by_name<N>(name: N, conn: &Connection) -> SqlResult<Option<Self>>
+    where
+        N: ToSql,
+    {
+        conn.prepare("SELECT * FROM `users` WHERE `name`=?1 LIMIT 1")?
+            .query_row(params! {name}, FromRow::from_row)
+            .optional()
+    }
+
+    pub fn all(conn: &Connection) -> SqlResult<Vec<Self>> {
+        conn.prepare("SELECT * FROM `users` ORDER BY `name` ASC")?
+            .query_map(params! {}, Self::from_row)?
+            .collect()
+    }
+}
@@ -0,0 +1,195 @@
+use super::*;
+use audit::Action;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// Delivery of an entry is given up after that many failed attempts.
+pub const MAX_ATTEMPTS: u32 = 10;
+
+/// Receives audit entries past `cursor` matching its filters.
+#[derive(Debug, FromRow, serde::Serialize)]
+pub struct Webhook {
+    #[serde(skip)]
+    pub id: i32,
+    pub name: String,
+    pub url: String,
+
+    #[serde(skip)]
+    pub secret: String,
+    /// comma separated actions, empty for all
+    pub events: String,
+    /// comma separated tag names, empty for any
+    pub tags: String,
+    /// id of the last delivered audit entry
+    pub cursor: i64,
+    /// failed attempts to deliver the entry after `cursor`
+    pub attempts: u32,
+    pub next_attempt_at: Option<NaiveDateTime>,
+    pub last_error: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+/// Seconds to wait after `attempts` failures: 5, 10, 20, ... up to an hour.
+pub fn backoff(attempts: u32) -> u32 {
+    (5u32 << attempts.saturating_sub(1).min(10)).min(60 * 60)
+}
+
+impl Webhook {
+    /// Starts from the current end of the audit log, secret is generated when absent.
+    pub fn create<N, U>(
+        name: N,
+        url: U,
+        secret: Option<&str>,
+        events: &str,
+        tags: &str,
+        conn: &Connection,
+    ) -> SqlResult<Self>
+    where
+        N: ToSql,
+        U: ToSql,
+    {
+        let secret = secret.map_or_else(token::generate_secret, ToOwned::to_owned);
+
+        conn.execute(
+            "INSERT INTO `webhooks` (name, url, secret, events, tags, cursor) VALUES(?1, ?2, ?3, ?4, ?5, ?6)",
+            params! {name, url, secret, events, tags, Audit::last_id(conn)?},
+        )?;
+
+        tagz_cg_serv::last_inserted!(&conn, "webhooks")
+    }
+
+    pub fn update(&self, conn: &Connection) -> SqlResult<()> {
+        conn.execute(
+            "UPDATE `webhooks` SET name=?2, url=?3, secret=?4, events=?5, tags=?6 WHERE `id`=?1",
+            params! {self.id, self.name, self.url, self.secret, self.events, self.tags},
+        )
+        .map(|_| ())
+    }
+
+    pub fn delete(&self, conn: &Connection) -> SqlResult<()> {
+        conn.execute("DELETE FROM `webhooks` WHERE `id`=?1", params! { self.id })
+            .map(|_| ())
+    }
+
+    /// Marks entries up to `id` as delivered.
+    pub fn advance(&mut self, id: i64, conn: &Connection) -> SqlResult<()> {
+        conn.execute(
+            "UPDATE `webhooks` SET cursor=?2, attempts=0, next_attempt_at=NULL, last_error=NULL WHERE `id`=?1",
+            params! {self.id, id},
+        )?;
+
+        self.cursor = id;
+        self.attempts = 0;
+        self.next_attempt_at = None;
+        self.last_error = None;
+
+        Ok(())
+    }
+
+    /// Schedules next attempt with exponential backoff.
+    pub fn fail(&mut self, error: &str, conn: &Connection) -> SqlResult<()> {
+        self.attempts += 1;
+        self.last_error = Some(error.to_owned());
+
+        conn.execute(
+            "UPDATE `webhooks` SET attempts=?2, next_attempt_at=datetime('now', ?3), last_error=?4 WHERE `id`=?1",
+            params! {self.id, self.attempts, format!("+{} seconds", backoff(self.attempts)), error},
+        )?;
+
+        self.next_attempt_at = conn.query_row(
+            "SELECT next_attempt_at FROM `webhooks` WHERE `id`=?1",
+            params! {self.id},
+            |row| row.get(0),
+        )?;
+
+        Ok(())
+    }
+
+    /// Hex of HMAC-SHA256 of `body` keyed with the secret.
+    pub fn sign(&self, body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_varkey(self.secret.as_bytes())
+            .expect("HMAC accepts keys of any size");
+        mac.input(body);
+
+        mac.result()
+            .code()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    pub fn matches(&self, entry: &Audit) -> bool {
+        self.matches_event(entry.action) && self.matches_tags(entry)
+    }
+
+    fn matches_event(&self, action: Action) -> bool {
+        self.events.is_empty() || self.events.split(',').any(|x| x == action.as_str())
+    }
+
+    /// Tags are looked up in the snapshot: name of a tag, tag of a relation
+    /// or tags of a file.
+    fn matches_tags(&self, entry: &Audit) -> bool {
+        if self.tags.is_empty() {
+            return true;
+        }
+
+        let snapshot = match entry.after.as_ref().or_else(|| entry.before.as_ref()) {
+            Some(snapshot) => &snapshot.0,
+            None => return false,
+        };
+        let names = match entry.action {
+            Action::TagCreate | Action::TagUpdate | Action::TagDelete | Action::TagRestore => {
+                vec![&snapshot["name"]]
+            }
+            Action::FileTagCreate | Action::FileTagDelete => vec![&snapshot["tag"]],
+            Action::FileCreate | Action::FileDelete | Action::FileRestore => snapshot["tags"]
+                .as_array()
+                .map(|tags| tags.iter().collect())
+                .unwrap_or_default(),
+        };
+
+        names
+            .iter()
+            .filter_map(|name| name.as_str())
+            .any(|name| self.tags.split(',').any(|x| x == name))
+    }
+
+    pub fn extract_from_name<N>(name: N, conn: &Connection) -> library::Result<Self>
+    where
+        N: ToSql,
+    {
+        Self::find_by_name(name, conn)?.ok_or(library::Error::WebhookNotFound)
+    }
+
+    pub fn find_by_name<N>(name: N, conn: &Connection) -> SqlResult<Option<Self>>
+    where
+        N: ToSql,
+    {
+        conn.prepare("SELECT * FROM `webhooks` WHERE `name`=?1 LIMIT 1")?
+            .query_row(params! {name}, FromRow::from_row)
+            .optional()
+    }
+
+    pub fn name_exists<N>(name: N, conn: &Connection) -> SqlResult<bool>
+    where
+        N: ToSql,
+    {
+        conn.prepare("SELECT 1 FROM `webhooks` WHERE `name`=?1 LIMIT 1")?
+            .query_row(params! {name}, |row| row.get(0))
+            .optional()
+            .map(|x: Option<i32>| x.is_some())
+    }
+
+    pub fn all(conn: &Connection) -> SqlResult<Vec<Self>> {
+        conn.prepare("SELECT * FROM `webhooks` ORDER BY `name` ASC")?
+            .query_map(params! {}, Self::from_row)?
+            .collect()
+    }
+
+    /// Webhooks not waiting for a retry.
+    pub fn all_due(conn: &Connection) -> SqlResult<Vec<Self>> {
+        conn.prepare("SELECT * FROM `webhooks` WHERE `next_attempt_at` IS NULL OR `next_attempt_at`<=CURRENT_TIMESTAMP")?
+            .query_map(params! {}, Self::from_row)?
+            .collect()
+    }
+}
@@ -0,0 +1,39 @@
+//! Walking directories for tags other tools keep along with files.
+
+use crate::library;
+use std::{fs, path::Path};
+
+/// Calls `f` with absolute path of every regular file under `dir`,
+/// unreadable directories are skipped.
+pub fn walk<F>(dir: &Path, mut f: F) -> library::Result<()>
+where
+    F: FnMut(&Path) -> library::Result<()>,
+{
+    let mut dirs = match fs::canonicalize(dir) {
+        Ok(dir) => vec![dir],
+        Err(err) => {
+            log::warn!("Cannot scan {}: {}", dir.display(), err);
+            return Ok(());
+        }
+    };
+
+    while let Some(dir) = dirs.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(err) => {
+                log::warn!("Cannot scan {}: {}", dir.display(), err);
+                continue;
+            }
+        };
+
+        for entry in entries.filter_map(Result::ok) {
+            match entry.file_type() {
+                Ok(kind) if kind.is_dir() => dirs.push(entry.path()),
+                Ok(kind) if kind.is_file() => f(&entry.path())?,
+                _ => {}
+            }
+        }
+    }
+
+    Ok(())
+}
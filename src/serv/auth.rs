@@ -0,0 +1,267 @@
+use super::{service_error::ErrorCode, ServiceError};
+use crate::{models, Connection};
+use actix_service::{Service, Transform};
+use actix_web::{
+    dev::{Payload, ServiceRequest, ServiceResponse},
+    http::header,
+    web::Data,
+    Error, FromRequest, HttpMessage, HttpRequest,
+};
+use futures::{
+    future::{ok, Ready},
+    lock::Mutex,
+    Future,
+};
+use std::{
+    cell::RefCell,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+};
+
+/// Who performs the request, resolved by [`Authentication`].
+#[derive(Clone, Debug)]
+pub struct Caller {
+    pub user: Option<models::User>,
+    /// role of the user
+    pub permission: models::Permission,
+    /// limit set by the token scope
+    pub scope: models::Scope,
+    /// name of the token used
+    pub token: Option<String>,
+    pub read_only: bool,
+}
+
+impl Caller {
+    /// Used while no tokens exist on a local target or with `--insecure-no-auth`.
+    pub fn unrestricted() -> Self {
+        Self {
+            user: None,
+            permission: models::Permission::Admin,
+            scope: models::Scope::Admin,
+            token: None,
+            read_only: false,
+        }
+    }
+
+    /// Name recorded in audit: user, token without user or `anonymous`.
+    pub fn actor(&self) -> String {
+        match (&self.user, &self.token) {
+            (Some(user), _) => user.name.clone(),
+            (None, Some(token)) => ["token:", token].concat(),
+            (None, None) => "anonymous".to_owned(),
+        }
+    }
+
+    pub fn user_id(&self) -> Option<i32> {
+        self.user.as_ref().map(|user| user.id)
+    }
+
+    /// Role narrowed by token scope and read-only mode.
+    pub fn effective(&self) -> models::Permission {
+        let permission = self.permission.min(self.scope.permission());
+
+        if self.read_only {
+            permission.min(models::Permission::Read)
+        } else {
+            permission
+        }
+    }
+
+    pub fn require(&self, permission: models::Permission) -> Result<(), ServiceError> {
+        if self.effective() >= permission {
+            Ok(())
+        } else if self.read_only {
+            Err(ErrorCode::ReadOnly.into())
+        } else if self.scope.permission() < permission {
+            Err(ErrorCode::InsufficientScope.into())
+        } else {
+            Err(ErrorCode::PermissionDenied.into())
+        }
+    }
+
+    pub fn access(&self) -> models::Access {
+        match self.user {
+            Some(ref user) if self.permission < models::Permission::Admin => {
+                models::Access::User(user.id)
+            }
+            _ => models::Access::All,
+        }
+    }
+
+    /// Library acting on behalf of the caller.
+    pub fn session(&self) -> crate::Session {
+        crate::Session {
+            user_id: self.user_id(),
+            access: self.access(),
+            admin: self.effective() >= models::Permission::Admin,
+            actor: self.actor(),
+        }
+    }
+
+    /// Unowned entries may be changed by anyone with `tag` permission,
+    /// owned ones only by their owner or an admin.
+    pub fn require_owner(&self, owner_id: Option<i32>) -> Result<(), ServiceError> {
+        self.require(models::Permission::Tag)?;
+
+        match owner_id {
+            Some(owner_id)
+                if self.effective() < models::Permission::Admin
+                    && self.user_id() != Some(owner_id) =>
+            {
+                Err(ErrorCode::PermissionDenied.into())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl FromRequest for Caller {
+    type Error = Error;
+    type Future = Ready<Result<Self, Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ok(req
+            .extensions()
+            .get::<Caller>()
+            .cloned()
+            .unwrap_or_else(Caller::unrestricted))
+    }
+}
+
+/// Requires `Authorization: Bearer <token>`. A database without tokens is
+/// open only if `anonymous` is set, see [`AppConfig::allows_anonymous`].
+///
+/// [`AppConfig::allows_anonymous`]: crate::AppConfig::allows_anonymous
+pub struct Authentication {
+    connection: Data<Mutex<Connection>>,
+    read_only: bool,
+    anonymous: bool,
+}
+
+impl Authentication {
+    pub fn new(connection: Data<Mutex<Connection>>, read_only: bool, anonymous: bool) -> Self {
+        Self {
+            connection,
+            read_only,
+            anonymous,
+        }
+    }
+}
+
+impl<S, B> Transform<S> for Authentication
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = AuthenticationMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(AuthenticationMiddleware {
+            service: Rc::new(RefCell::new(service)),
+            connection: Data::clone(&self.connection),
+            read_only: self.read_only,
+            anonymous: self.anonymous,
+        })
+    }
+}
+
+pub struct AuthenticationMiddleware<S> {
+    service: Rc<RefCell<S>>,
+    connection: Data<Mutex<Connection>>,
+    read_only: bool,
+    anonymous: bool,
+}
+
+impl<S, B> Service for AuthenticationMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.service.borrow_mut().poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let connection = Data::clone(&self.connection);
+        let read_only = self.read_only;
+        let anonymous = self.anonymous;
+
+        Box::pin(async move {
+            let secret = bearer(&req).map(ToOwned::to_owned);
+
+            match authenticate(secret.as_deref(), anonymous, &*connection.lock().await) {
+                Ok(caller) => {
+                    req.extensions_mut().insert(Caller {
+                        read_only,
+                        ..caller
+                    });
+                }
+                Err(err) => return Ok(req.error_response(err)),
+            }
+
+            let fut = service.borrow_mut().call(req);
+            fut.await
+        })
+    }
+}
+
+fn bearer(req: &ServiceRequest) -> Option<&str> {
+    let value = req.headers().get(header::AUTHORIZATION)?.to_str().ok()?;
+
+    if value.len() > 7 && value[..7].eq_ignore_ascii_case("bearer ") {
+        Some(value[7..].trim())
+    } else {
+        None
+    }
+}
+
+fn authenticate(
+    secret: Option<&str>,
+    anonymous: bool,
+    conn: &Connection,
+) -> Result<Caller, ServiceError> {
+    if anonymous && !models::Token::any_exists(conn)? {
+        return Ok(Caller::unrestricted());
+    }
+
+    let token = match secret {
+        Some(secret) => models::Token::find_by_secret(secret, conn)?,
+        None => None,
+    }
+    .ok_or_else(|| ServiceError::from(ErrorCode::Unauthorized))?;
+
+    token.touch(conn)?;
+
+    let user = match token.user_id {
+        Some(user_id) => Some(
+            models::User::find_by_id(user_id, conn)?
+                .ok_or_else(|| ServiceError::from(ErrorCode::Unauthorized))?,
+        ),
+        None => None,
+    };
+
+    Ok(Caller {
+        permission: user
+            .as_ref()
+            .map_or(models::Permission::Admin, |user| user.role),
+        user,
+        scope: token.scope,
+        token: Some(token.name),
+        read_only: false,
+    })
+}
@@ -0,0 +1,85 @@
+//! Drop-in `Json` and `Query` extractors keeping the path of the value serde
+//! failed on, which `web::Json` and `web::Query` lose. It is reported as
+//! `field` of [`ErrorCode::InvalidJson`] and [`ErrorCode::InvalidQuery`].
+//!
+//! [`ErrorCode::InvalidJson`]: super::service_error::ErrorCode::InvalidJson
+//! [`ErrorCode::InvalidQuery`]: super::service_error::ErrorCode::InvalidQuery
+
+use super::ServiceError;
+use actix_web::{dev::Payload, web, FromRequest, HttpRequest};
+use futures::future::{ready, FutureExt, LocalBoxFuture, Ready};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::ops::{Deref, DerefMut};
+
+macro_rules! wrapper {
+    ($name:ident) => {
+        pub struct $name<T>(pub T);
+
+        impl<T> $name<T> {
+            pub fn into_inner(self) -> T {
+                self.0
+            }
+        }
+
+        impl<T> Deref for $name<T> {
+            type Target = T;
+
+            fn deref(&self) -> &T {
+                &self.0
+            }
+        }
+
+        impl<T> DerefMut for $name<T> {
+            fn deref_mut(&mut self) -> &mut T {
+                &mut self.0
+            }
+        }
+    };
+}
+
+wrapper!(Json);
+wrapper!(Query);
+
+/// Body is parsed by `web::Json` first, so syntax errors, size and content
+/// type are reported by `web::JsonConfig` as before.
+impl<T> FromRequest for Json<T>
+where
+    T: DeserializeOwned + 'static,
+{
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let value = web::Json::<Value>::from_request(req, payload);
+
+        async move {
+            serde_path_to_error::deserialize(value.await?.into_inner())
+                .map(Json)
+                .map_err(|err| ServiceError::from(err).into())
+        }
+        .boxed_local()
+    }
+}
+
+impl<T> FromRequest for Query<T>
+where
+    T: DeserializeOwned,
+{
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let query = serde_urlencoded::Deserializer::new(url::form_urlencoded::parse(
+            req.query_string().as_bytes(),
+        ));
+
+        ready(
+            serde_path_to_error::deserialize(query)
+                .map(Query)
+                .map_err(|err| ServiceError::from(err).into()),
+        )
+    }
+}
@@ -0,0 +1,137 @@
+//! Languages of error messages, chosen by `Accept-Language` header.
+
+use super::ServiceError;
+use actix_service::{Service, Transform};
+use actix_web::{
+    dev::{Body, ServiceRequest, ServiceResponse},
+    http::header,
+    Error, HttpResponse,
+};
+use futures::{
+    future::{ok, Ready},
+    Future,
+};
+use std::{
+    pin::Pin,
+    str::FromStr,
+    task::{Context, Poll},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lang {
+    En,
+    Ru,
+}
+
+impl Lang {
+    /// Most preferred supported language of `Accept-Language` value,
+    /// like `ru-RU,ru;q=0.9,en;q=0.8`.
+    pub fn from_accept_language(value: &str) -> Option<Self> {
+        let mut best: Option<(Self, f32)> = None;
+
+        for range in value.split(',') {
+            let mut parts = range.split(';').map(str::trim);
+            let lang = match parts.next().and_then(|tag| tag.parse().ok()) {
+                Some(lang) => lang,
+                None => continue,
+            };
+            let quality = parts
+                .find_map(|param| param.strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.parse::<f32>().ok())
+                .unwrap_or(0.0);
+
+            if quality > 0.0 && best.map_or(true, |(_, best)| quality > best) {
+                best = Some((lang, quality));
+            }
+        }
+
+        best.map(|(lang, _)| lang)
+    }
+}
+
+impl FromStr for Lang {
+    type Err = ();
+
+    fn from_str(tag: &str) -> Result<Self, Self::Err> {
+        // primary subtag, `ru` of `ru-RU`
+        match tag
+            .split('-')
+            .next()
+            .ok_or(())?
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "en" => Ok(Lang::En),
+            "ru" => Ok(Lang::Ru),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Translates messages of [`ServiceError`] responses, others are left as they are.
+pub struct Localization;
+
+impl<S> Transform<S> for Localization
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<Body>, Error = Error> + 'static,
+    S::Future: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<Body>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = LocalizationMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(LocalizationMiddleware { service })
+    }
+}
+
+pub struct LocalizationMiddleware<S> {
+    service: S,
+}
+
+impl<S> Service for LocalizationMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<Body>, Error = Error> + 'static,
+    S::Future: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<Body>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let lang = req
+            .headers()
+            .get(header::ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(Lang::from_accept_language)
+            .unwrap_or(Lang::En);
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let res = fut.await?;
+
+            if lang == Lang::En {
+                return Ok(res);
+            }
+
+            let err = match res
+                .response()
+                .error()
+                .and_then(|err| err.as_error::<ServiceError>())
+            {
+                Some(err) => err.clone().localized(lang),
+                None => return Ok(res),
+            };
+
+            Ok(res.into_response(HttpResponse::from_error(err.into())))
+        })
+    }
+}
@@ -7,42 +7,91 @@ use futures::lock::Mutex;
 use routes::api as apis;
 pub use service_error::ServiceError;
 
+pub mod auth;
+pub mod extract;
+pub mod i18n;
+pub mod openapi;
 pub mod routes;
 pub mod service_error;
+pub mod tls;
+#[cfg(unix)]
+pub mod uds;
+pub mod webhooks;
 
 #[inline]
 pub async fn run(connection: Connection, cfg: AppConfig) -> std::io::Result<()> {
     let connection = Data::new(Mutex::new(connection));
     let cfg = Data::new(cfg);
 
+    actix_rt::spawn(webhooks::deliver_forever(Data::clone(&connection)));
+
+    if cfg.trash_days > 0 {
+        actix_rt::spawn(purge_trash(Data::clone(&connection), cfg.trash_days));
+    }
+
     let http_target = cfg.http_target.clone(); // FIXME: remove?
+    #[cfg(unix)]
+    let socket_mode = cfg.socket_mode;
+    let tls = match (&cfg.tls_cert, &cfg.tls_key) {
+        (Some(cert), Some(key)) => Some(std::sync::Arc::new(tls::ReloadableCert::load(cert, key)?)),
+        _ => None,
+    };
 
     #[rustfmt::skip]
-    HttpServer::new(move || {
+    let server = HttpServer::new(move || {
         App::new()
             .app_data(Data::clone(&connection))
             .app_data(Data::clone(&cfg))
+            .app_data(web::JsonConfig::default().error_handler(|err, _| ServiceError::from(err).into()))
+            .app_data(web::PathConfig::default().error_handler(service_error::path_error))
+            .wrap(auth::Authentication::new(Data::clone(&connection), cfg.read_only, cfg.allows_anonymous()))
+            .wrap(i18n::Localization)
             .wrap(actix_web::middleware::Logger::default())
-            .service(web::scope("api")
-                .service(web::scope("v1")
-                    .service(web::scope("tags")
-                        .service(apis::tags::create)
-                        .service(apis::tags::delete)
-                        .service(apis::tags::list)
-                    )
-                    .service(web::scope("files")
-                        .service(apis::files::create)
-                        .service(apis::files::delete)
-                        .service(apis::files::list)
-
-                        .service(web::scope("{file_id}")
-                            .service(apis::files::add)
-                            .service(apis::files::remove)
-                            )
-                    ),
-            ))
-    })
-    .bind(http_target.as_ref())?
-    .run()
-    .await
+            .service(web::scope("api/v1").configure(apis::configure))
+    });
+
+    #[cfg(unix)]
+    {
+        if let Some(path) = uds::socket_path(&http_target) {
+            if tls.is_some() {
+                log::warn!("TLS is not used for unix socket {}.", path.display());
+            }
+
+            uds::remove_stale(path)?;
+
+            let server = uds::with_mode(socket_mode, || server.bind_uds(path))?;
+
+            let res = server.run().await;
+            std::fs::remove_file(path).ok();
+
+            return res;
+        }
+    }
+
+    let server = match tls {
+        Some(cert) => {
+            #[cfg(unix)]
+            tls::reload_on_sighup(std::sync::Arc::clone(&cert))?;
+
+            server.bind_rustls(http_target.as_ref(), tls::server_config(cert))?
+        }
+        None => server.bind(http_target.as_ref())?,
+    };
+
+    server.run().await
+}
+
+/// Purges expired trash on start and then hourly.
+async fn purge_trash(connection: Data<Mutex<Connection>>, days: u32) {
+    let mut interval = actix_rt::time::interval(std::time::Duration::from_secs(60 * 60));
+
+    loop {
+        interval.tick().await;
+
+        match crate::models::Trash::purge_older_than(days, &*connection.lock().await) {
+            Ok(0) => {}
+            Ok(purged) => log::info!("Purged {} trash entries.", purged),
+            Err(err) => log::error!("Could not purge trash: {}", err),
+        }
+    }
 }
@@ -0,0 +1,513 @@
+//! OpenAPI 3 description of `api/v1`, served at `/api/v1/openapi.json`.
+//!
+//! Shapes follow serialized models and request types of `routes::api`,
+//! every route registered in [`super::run`] must be described here, which
+//! is checked by tests.
+//! Names in paths may contain `/` escaped as `%2F`.
+
+use super::service_error::ErrorCode;
+use serde_json::{json, Map, Value};
+
+lazy_static! {
+    pub static ref DOCUMENT: Value = document();
+}
+
+fn schema(name: &str) -> Value {
+    json!({ "$ref": (["#/components/schemas/", name].concat()) })
+}
+
+fn array(items: Value) -> Value {
+    json!({ "type": "array", "items": items })
+}
+
+fn object(required: &[&str], properties: Value) -> Value {
+    json!({ "type": "object", "required": required, "properties": properties })
+}
+
+fn string() -> Value {
+    json!({ "type": "string" })
+}
+
+fn integer() -> Value {
+    json!({ "type": "integer" })
+}
+
+fn boolean() -> Value {
+    json!({ "type": "boolean" })
+}
+
+/// Timestamps carry no offset, so they are not RFC 3339 `date-time`.
+fn datetime() -> Value {
+    json!({
+        "type": "string",
+        "pattern": r"^\d{4}-\d{2}-\d{2}T\d{2}:\d{2}:\d{2}(\.\d+)?$",
+        "description": "UTC date and time without offset, like `2020-01-31T12:00:00`",
+    })
+}
+
+fn nullable(mut value: Value) -> Value {
+    value["nullable"] = json!(true);
+    value
+}
+
+fn enumeration(values: &[&str]) -> Value {
+    json!({ "type": "string", "enum": values })
+}
+
+/// Codes along with HTTP statuses they are sent with.
+fn error_codes() -> Value {
+    let mut codes = enumeration(
+        &ErrorCode::ALL
+            .iter()
+            .map(|code| code.as_str())
+            .collect::<Vec<_>>(),
+    );
+    codes["description"] = json!(ErrorCode::ALL
+        .iter()
+        .map(|code| format!("`{}`: {}", code.as_str(), code.http_status().as_u16()))
+        .collect::<Vec<_>>()
+        .join(", "));
+    codes
+}
+
+fn path_param(name: &str, value: Value) -> Value {
+    json!({ "name": name, "in": "path", "required": true, "schema": value })
+}
+
+fn query_param(name: &str, required: bool, value: Value) -> Value {
+    json!({ "name": name, "in": "query", "required": required, "schema": value })
+}
+
+/// Successful JSON response wrapped into `{"Ok": ...}` envelope.
+fn ok(status: &str, description: &str, value: Value) -> (String, Value) {
+    (
+        status.to_owned(),
+        json!({
+            "description": description,
+            "content": { "application/json": { "schema": object(&["Ok"], json!({ "Ok": value })) } },
+        }),
+    )
+}
+
+fn no_content() -> (String, Value) {
+    ("204".to_owned(), json!({ "description": "Done" }))
+}
+
+/// Builds operation, errors are `{"Err": ServiceError}` for any other status.
+fn operation(
+    summary: &str,
+    permission: &str,
+    parameters: Vec<Value>,
+    body: Option<Value>,
+    responses: Vec<(String, Value)>,
+) -> Value {
+    let mut responses = responses.into_iter().collect::<Map<String, Value>>();
+    responses.insert(
+        "default".to_owned(),
+        json!({
+            "description": "Error",
+            "content": { "application/json": { "schema": schema("ErrorEnvelope") } },
+        }),
+    );
+
+    let mut op = json!({
+        "summary": summary,
+        "description": (["Requires `", permission, "` permission."].concat()),
+        "parameters": parameters,
+        "responses": responses,
+    });
+
+    if let Some(body) = body {
+        op["requestBody"] = json!({
+            "required": true,
+            "content": { "application/json": { "schema": body } },
+        });
+    }
+
+    op
+}
+
+fn page() -> Value {
+    query_param("page", true, json!({ "type": "integer", "minimum": 0 }))
+}
+
+fn sort() -> Value {
+    enumeration(&["newest", "oldest", "name", "name_desc", "updated"])
+}
+
+fn actions() -> Value {
+    enumeration(&[
+        "file.create",
+        "file.delete",
+        "file.restore",
+        "tag.create",
+        "tag.update",
+        "tag.delete",
+        "tag.restore",
+        "file_tag.create",
+        "file_tag.delete",
+    ])
+}
+
+fn schemas() -> Value {
+    json!({
+        "ServiceError": object(&["status", "message"], json!({
+            "status": error_codes(),
+            "message": { "type": "string", "description": "Localized by `Accept-Language`, `en` and `ru` are supported" },
+            "details": {},
+        })),
+        "ErrorEnvelope": object(&["Err"], json!({ "Err": schema("ServiceError") })),
+        "File": object(&["id", "name", "shared", "tags", "updated_at", "created_at"], json!({
+            "id": integer(),
+            "name": string(),
+            "owner_id": nullable(integer()),
+            "shared": boolean(),
+            "tags": array(string()),
+            "updated_at": datetime(),
+            "created_at": datetime(),
+        })),
+        "Tag": object(&["id", "name", "shared", "created_at"], json!({
+            "id": integer(),
+            "name": string(),
+            "description": nullable(string()),
+            "owner_id": nullable(integer()),
+            "shared": boolean(),
+            "created_at": datetime(),
+        })),
+        "RelatedTag": object(&["name", "score", "occurrences"], json!({
+            "name": string(),
+            "score": { "type": "number" },
+            "occurrences": integer(),
+        })),
+        "Facet": object(&["name", "count"], json!({
+            "name": string(),
+            "count": integer(),
+        })),
+        "FacetedList": object(&["files", "total", "facets"], json!({
+            "files": array(schema("File")),
+            "total": integer(),
+            "facets": array(schema("Facet")),
+        })),
+        "SavedSearch": object(&["name", "tags", "sort", "exact", "shared", "updated_at", "created_at"], json!({
+            "name": string(),
+            "tags": { "type": "string", "description": "comma separated tag names" },
+            "query": nullable(string()),
+            "sort": sort(),
+            "exact": boolean(),
+            "owner_id": nullable(integer()),
+            "shared": boolean(),
+            "updated_at": datetime(),
+            "created_at": datetime(),
+        })),
+        "Collection": object(&["name", "shared", "files", "updated_at", "created_at"], json!({
+            "name": string(),
+            "owner_id": nullable(integer()),
+            "shared": boolean(),
+            "files": array(schema("File")),
+            "updated_at": datetime(),
+            "created_at": datetime(),
+        })),
+        "Position": object(&["position"], json!({ "position": integer() })),
+        "Audit": object(&["id", "actor", "action", "created_at"], json!({
+            "id": integer(),
+            "actor": string(),
+            "action": actions(),
+            "before": { "nullable": true, "description": "snapshot of the entry" },
+            "after": { "nullable": true, "description": "snapshot of the entry" },
+            "created_at": datetime(),
+        })),
+        "Trash": object(&["id", "kind", "name", "shared", "entry", "relations", "actor", "deleted_at"], json!({
+            "id": integer(),
+            "kind": enumeration(&["file", "tag"]),
+            "name": string(),
+            "owner_id": nullable(integer()),
+            "shared": boolean(),
+            "entry": { "description": "snapshot of the deleted entry" },
+            "relations": {
+                "description": "ids of files of a tag, or `tags` ids and `collections` `[collection_id, position]` pairs of a file",
+            },
+            "actor": string(),
+            "deleted_at": datetime(),
+        })),
+        "Webhook": object(&["name", "url", "events", "tags", "cursor", "attempts", "created_at"], json!({
+            "name": string(),
+            "url": string(),
+            "events": { "type": "string", "description": "comma separated actions, empty for all" },
+            "tags": { "type": "string", "description": "comma separated tag names, empty for any" },
+            "cursor": integer(),
+            "attempts": integer(),
+            "next_attempt_at": nullable(datetime()),
+            "last_error": nullable(string()),
+            "created_at": datetime(),
+        })),
+        "CreatedWebhook": {
+            "allOf": [
+                schema("Webhook"),
+                object(&["secret"], json!({ "secret": string() })),
+            ],
+        },
+        "NewFile": object(&["name", "tags"], json!({
+            "name": string(),
+            "tags": array(string()),
+            "shared": boolean(),
+        })),
+        "NewTag": object(&["name"], json!({
+            "name": string(),
+            "description": string(),
+            "shared": boolean(),
+        })),
+        "NewSearch": object(&["name"], json!({
+            "name": string(),
+            "tags": { "type": "string", "description": "comma separated tag names" },
+            "q": string(),
+            "sort": sort(),
+            "exact": boolean(),
+            "shared": { "type": "boolean", "description": "kept on update when absent" },
+        })),
+        "NewCollection": object(&["name"], json!({
+            "name": string(),
+            "files": array(integer()),
+            "shared": boolean(),
+        })),
+        "NewWebhook": object(&["name", "url"], json!({
+            "name": string(),
+            "url": string(),
+            "secret": string(),
+            "events": array(actions()),
+            "tags": { "type": "string", "description": "comma separated tag names" },
+        })),
+    })
+}
+
+fn paths() -> Value {
+    let name = || path_param("name", string());
+    let file_id = || path_param("file_id", integer());
+    let files_or_facets = || {
+        ok(
+            "200",
+            "Files, or files with facets when `facets=true`",
+            json!({ "oneOf": [array(schema("File")), schema("FacetedList")] }),
+        )
+    };
+
+    json!({
+        "/openapi.json": {
+            "get": {
+                "summary": "This document",
+                "responses": { "200": { "description": "OpenAPI document" } },
+            },
+        },
+        "/audit": {
+            "get": operation("List audit entries, newest first", "admin", vec![
+                page(),
+                query_param("actor", false, string()),
+                query_param("action", false, actions()),
+                query_param("since", false, datetime()),
+                query_param("until", false, datetime()),
+            ], None, vec![ok("200", "Entries", array(schema("Audit")))]),
+        },
+        "/events": {
+            "get": {
+                "summary": "Server-sent events of audit entries, `event` is the action and `id` the audit id",
+                "description": "Requires `read` permission. Resumes after `since` or `Last-Event-ID` header.",
+                "parameters": [
+                    query_param("since", false, integer()),
+                    { "name": "Last-Event-ID", "in": "header", "required": false, "schema": integer() },
+                ],
+                "responses": {
+                    "200": { "description": "Event stream", "content": { "text/event-stream": { "schema": string() } } },
+                    "default": { "description": "Error", "content": { "application/json": { "schema": schema("ErrorEnvelope") } } },
+                },
+            },
+        },
+        "/tags": {
+            "get": operation("List names of visible tags", "read", vec![], None,
+                vec![ok("200", "Tag names", array(string()))]),
+            "post": operation("Create tag", "tag", vec![], Some(schema("NewTag")),
+                vec![ok("200", "Created tag", schema("Tag"))]),
+        },
+        "/tags/{name}": {
+            "put": operation("Rename tag", "tag", vec![name()],
+                Some(object(&["name"], json!({ "name": string() }))),
+                vec![ok("200", "Renamed tag", schema("Tag"))]),
+            "delete": operation("Move tag to trash", "tag", vec![
+                name(),
+                query_param("confirm", false, boolean()),
+            ], None, vec![no_content()]),
+        },
+        "/tags/related": {
+            "get": operation("Tags co-occurring with given ones", "read", vec![
+                query_param("tags", true, string()),
+                query_param("limit", false, integer()),
+            ], None, vec![ok("200", "Ranked tags", array(schema("RelatedTag")))]),
+        },
+        "/tags/search": {
+            "get": operation("Full-text search over tag names and descriptions", "read", vec![
+                query_param("q", true, string()),
+            ], None, vec![ok("200", "Tags", array(schema("Tag")))]),
+        },
+        "/files": {
+            "get": operation("List files by tags and text", "read", vec![
+                page(),
+                query_param("tags", false, string()),
+                query_param("q", false, string()),
+                query_param("exact", false, boolean()),
+                query_param("sort", false, sort()),
+                query_param("facets", false, boolean()),
+            ], None, vec![files_or_facets()]),
+            "post": operation("Create file with tags", "tag", vec![], Some(schema("NewFile")),
+                vec![ok("200", "Created file", schema("File"))]),
+        },
+        "/files/{name}": {
+            "get": operation("Show file with its tags", "read", vec![name()], None,
+                vec![ok("200", "File", schema("File"))]),
+            "delete": operation("Move file to trash", "tag", vec![name()], None, vec![no_content()]),
+        },
+        "/files/{file_id}/related": {
+            "get": operation("Tags to suggest for a file", "read", vec![
+                file_id(),
+                query_param("limit", false, integer()),
+            ], None, vec![ok("200", "Ranked tags", array(schema("RelatedTag")))]),
+        },
+        "/files/{file_id}/{name}": {
+            "post": operation("Add tag to file", "tag", vec![file_id(), name()], None, vec![no_content()]),
+            "delete": operation("Remove tag from file", "tag", vec![file_id(), name()], None, vec![no_content()]),
+        },
+        "/searches": {
+            "get": operation("List saved searches", "read", vec![], None,
+                vec![ok("200", "Saved searches", array(schema("SavedSearch")))]),
+            "post": operation("Save search", "tag", vec![], Some(schema("NewSearch")),
+                vec![ok("200", "Saved search", schema("SavedSearch"))]),
+        },
+        "/searches/{name}": {
+            "get": operation("Show saved search", "read", vec![name()], None,
+                vec![ok("200", "Saved search", schema("SavedSearch"))]),
+            "put": operation("Replace saved search", "tag", vec![name()], Some(schema("NewSearch")),
+                vec![ok("200", "Saved search", schema("SavedSearch"))]),
+            "delete": operation("Delete saved search", "tag", vec![name()], None, vec![no_content()]),
+        },
+        "/searches/{name}/files": {
+            "get": operation("Evaluate saved search", "read", vec![
+                name(),
+                page(),
+                query_param("facets", false, boolean()),
+            ], None, vec![files_or_facets()]),
+        },
+        "/collections": {
+            "get": operation("List names of collections", "read", vec![], None,
+                vec![ok("200", "Collection names", array(string()))]),
+            "post": operation("Create collection", "tag", vec![], Some(schema("NewCollection")),
+                vec![ok("200", "Collection", schema("Collection"))]),
+        },
+        "/collections/{name}": {
+            "get": operation("Show collection with its files in order", "read", vec![name()], None,
+                vec![ok("200", "Collection", schema("Collection"))]),
+            "put": operation("Rename collection", "tag", vec![name()],
+                Some(object(&["name"], json!({ "name": string() }))), vec![no_content()]),
+            "delete": operation("Delete collection", "tag", vec![name()], None, vec![no_content()]),
+        },
+        "/collections/{name}/files": {
+            "post": operation("Insert file, appended when position is absent", "tag", vec![name()],
+                Some(object(&["file_id"], json!({ "file_id": integer(), "position": integer() }))),
+                vec![ok("201", "Position of the file", schema("Position"))]),
+        },
+        "/collections/{name}/files/{file_id}": {
+            "put": operation("Move file to position", "tag", vec![name(), file_id()],
+                Some(object(&["position"], json!({ "position": integer() }))),
+                vec![ok("200", "Position of the file", schema("Position"))]),
+            "delete": operation("Remove file from collection", "tag", vec![name(), file_id()], None,
+                vec![no_content()]),
+        },
+        "/collections/{name}/order": {
+            "put": operation("Reorder every file of collection", "tag", vec![name()],
+                Some(object(&["files"], json!({ "files": array(integer()) }))), vec![no_content()]),
+        },
+        "/trash": {
+            "get": operation("List trash, recently deleted first", "read", vec![page()], None,
+                vec![ok("200", "Trash entries", array(schema("Trash")))]),
+        },
+        "/trash/{id}/restore": {
+            "post": operation("Restore entry with its relations", "tag", vec![path_param("id", integer())], None,
+                vec![ok("200", "Restored file or tag", json!({ "oneOf": [schema("File"), schema("Tag")] }))]),
+        },
+        "/webhooks": {
+            "get": operation("List webhooks", "admin", vec![], None,
+                vec![ok("200", "Webhooks", array(schema("Webhook")))]),
+            "post": operation("Create webhook, secret is shown only once", "admin", vec![], Some(schema("NewWebhook")),
+                vec![ok("201", "Webhook", schema("CreatedWebhook"))]),
+        },
+        "/webhooks/{name}": {
+            "get": operation("Show webhook", "admin", vec![name()], None,
+                vec![ok("200", "Webhook", schema("Webhook"))]),
+            "put": operation("Replace webhook, secret is kept when absent", "admin", vec![name()], Some(schema("NewWebhook")),
+                vec![ok("200", "Webhook", schema("Webhook"))]),
+            "delete": operation("Delete webhook", "admin", vec![name()], None, vec![no_content()]),
+        },
+    })
+}
+
+fn document() -> Value {
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "TagZ",
+            "description": env!("CARGO_PKG_DESCRIPTION"),
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "servers": [{ "url": "/api/v1" }],
+        "security": [{ "bearer": [] }],
+        "paths": paths(),
+        "components": {
+            "schemas": schemas(),
+            "securitySchemes": {
+                "bearer": {
+                    "type": "http",
+                    "scheme": "bearer",
+                    "description": "Required unless no token exists and the server listens on a local address or socket, see `tagzd token create`.",
+                },
+            },
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    fn registered_routes() -> BTreeSet<(String, String)> {
+        crate::serv::routes::api::ROUTES
+            .iter()
+            .map(|(method, path)| (method.as_str().to_lowercase(), (*path).to_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn describes_every_route() {
+        let document = document();
+        for (method, path) in &registered_routes() {
+            assert!(
+                document["paths"][path][method].is_object(),
+                "`{} {}` is not described",
+                method.to_uppercase(),
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn describes_only_registered_routes() {
+        let routes = registered_routes();
+
+        for (path, item) in document()["paths"].as_object().unwrap() {
+            for method in item.as_object().unwrap().keys() {
+                assert!(
+                    routes.contains(&(method.to_owned(), path.to_owned())),
+                    "`{} {}` is not registered",
+                    method.to_uppercase(),
+                    path
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,35 @@
+use super::*;
+use chrono::NaiveDateTime;
+
+#[derive(Deserialize)]
+pub struct ListQuery {
+    pub page: u16,
+    pub actor: Option<Box<str>>,
+    pub action: Option<models::audit::Action>,
+    pub since: Option<NaiveDateTime>,
+    pub until: Option<NaiveDateTime>,
+}
+
+/// Entries may reveal private files and tags of everyone, so admins only.
+pub async fn list(
+    conn: ConnLock,
+    caller: Caller,
+    query: Query<ListQuery>,
+) -> Result<impl Responder> {
+    caller.require(Permission::Admin)?;
+
+    let per_page = *crate::config::LIST_AUDIT_PER_PAGE.lock().await;
+    let conn = conn.lock().await;
+
+    res::json!(models::Audit::find_specific_amount_by_filter_on_page(
+        &models::audit::Filter {
+            actor: query.actor.as_deref(),
+            action: query.action,
+            since: query.since,
+            until: query.until,
+        },
+        per_page,
+        query.page as u32,
+        &conn,
+    )?)
+}
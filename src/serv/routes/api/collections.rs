@@ -0,0 +1,178 @@
+use super::*;
+
+#[derive(Deserialize)]
+pub struct Collection {
+    pub name: Box<str>,
+    pub files: Option<Vec<i32>>,
+    pub shared: Option<bool>,
+}
+
+pub async fn create(
+    conn: ConnLock,
+    caller: Caller,
+    collectionj: Json<Collection>,
+) -> Result<impl Responder> {
+    caller.require(Permission::Tag)?;
+
+    let mut conn = conn.lock().await;
+
+    res::json!(Library::new(&mut conn, caller.session()).create_collection(
+        &collectionj.name,
+        collectionj.files.as_deref().unwrap_or_default(),
+        collectionj.shared.unwrap_or(true)
+    )?)
+}
+
+//---
+pub async fn list(conn: ConnLock, caller: Caller) -> Result<impl Responder> {
+    caller.require(Permission::Read)?;
+
+    let mut conn = conn.lock().await;
+
+    res::json!(Library::new(&mut conn, caller.session())
+        .collections()?
+        .iter()
+        .map(|collection| &collection.name)
+        .collect::<Box<[_]>>())
+}
+
+//---
+pub async fn show(
+    conn: ConnLock,
+    caller: Caller,
+    name: web::Path<Box<str>>,
+) -> Result<impl Responder> {
+    caller.require(Permission::Read)?;
+
+    let mut conn = conn.lock().await;
+
+    res::json!(Library::new(&mut conn, caller.session()).collection(&name)?)
+}
+
+//---
+#[derive(Deserialize)]
+pub struct Rename {
+    pub name: Box<str>,
+}
+
+pub async fn rename(
+    conn: ConnLock,
+    caller: Caller,
+    name: web::Path<Box<str>>,
+    renamej: Json<Rename>,
+) -> Result<impl Responder> {
+    caller.require(Permission::Tag)?;
+
+    let mut conn = conn.lock().await;
+
+    Library::new(&mut conn, caller.session()).rename_collection(&name, &renamej.name)?;
+
+    res::no_content!()
+}
+
+//---
+pub async fn delete(
+    conn: ConnLock,
+    caller: Caller,
+    name: web::Path<Box<str>>,
+) -> Result<impl Responder> {
+    caller.require(Permission::Tag)?;
+
+    let mut conn = conn.lock().await;
+
+    Library::new(&mut conn, caller.session()).delete_collection(&name)?;
+
+    res::no_content!()
+}
+
+//---
+#[derive(Deserialize)]
+pub struct Entry {
+    pub file_id: i32,
+    pub position: Option<u32>,
+}
+
+#[derive(serde::Serialize)]
+pub struct Position {
+    pub position: u32,
+}
+
+pub async fn add(
+    conn: ConnLock,
+    caller: Caller,
+    name: web::Path<Box<str>>,
+    entryj: Json<Entry>,
+) -> Result<impl Responder> {
+    caller.require(Permission::Tag)?;
+
+    let mut conn = conn.lock().await;
+    let position = Library::new(&mut conn, caller.session()).add_to_collection(
+        &name,
+        entryj.file_id,
+        entryj.position,
+    )?;
+
+    res::json!(CREATED, Position { position })
+}
+
+//---
+pub async fn remove(
+    conn: ConnLock,
+    caller: Caller,
+    info: web::Path<(Box<str>, i32)>,
+) -> Result<impl Responder> {
+    caller.require(Permission::Tag)?;
+
+    let mut conn = conn.lock().await;
+    let (name, file_id) = info.into_inner();
+
+    Library::new(&mut conn, caller.session()).remove_from_collection(&name, file_id)?;
+
+    res::no_content!()
+}
+
+//---
+#[derive(Deserialize)]
+pub struct Move {
+    pub position: u32,
+}
+
+pub async fn move_file(
+    conn: ConnLock,
+    caller: Caller,
+    info: web::Path<(Box<str>, i32)>,
+    movej: Json<Move>,
+) -> Result<impl Responder> {
+    caller.require(Permission::Tag)?;
+
+    let mut conn = conn.lock().await;
+    let (name, file_id) = info.into_inner();
+    let position = Library::new(&mut conn, caller.session()).move_in_collection(
+        &name,
+        file_id,
+        movej.position,
+    )?;
+
+    res::json!(Position { position })
+}
+
+//---
+#[derive(Deserialize)]
+pub struct Order {
+    pub files: Vec<i32>,
+}
+
+pub async fn reorder(
+    conn: ConnLock,
+    caller: Caller,
+    name: web::Path<Box<str>>,
+    orderj: Json<Order>,
+) -> Result<impl Responder> {
+    caller.require(Permission::Tag)?;
+
+    let mut conn = conn.lock().await;
+
+    Library::new(&mut conn, caller.session()).reorder_collection(&name, &orderj.files)?;
+
+    res::no_content!()
+}
@@ -0,0 +1,120 @@
+//! Server-sent events following the audit log, whose ids are event ids,
+//! so clients resume with `Last-Event-ID` header or `since` query.
+
+use super::*;
+use actix_web::{http::header, web::Bytes, HttpRequest};
+use std::time::Duration;
+
+const BATCH: u32 = 100;
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+#[derive(Deserialize)]
+pub struct StreamQuery {
+    /// id of the last seen event, only new events are sent by default
+    pub since: Option<i64>,
+}
+
+struct Feed {
+    conn: ConnLock,
+    access: models::Access,
+    last_id: i64,
+    poll: Duration,
+    idle: Duration,
+}
+
+fn last_event_id(req: &HttpRequest) -> Option<i64> {
+    req.headers()
+        .get("Last-Event-ID")?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// One `event` per entry, named after its action, e.g. `file_tag.create`.
+fn format_event(entry: &models::Audit) -> String {
+    format!(
+        "id: {}\nevent: {}\ndata: {}\n\n",
+        entry.id,
+        entry.action.as_str(),
+        serde_json::to_string(entry).unwrap_or_default()
+    )
+}
+
+/// Next chunk of events, `None` ends the stream on database failure.
+async fn next_events(
+    mut feed: Feed,
+) -> Option<(std::result::Result<Bytes, actix_web::Error>, Feed)> {
+    loop {
+        let chunk = {
+            let conn = feed.conn.lock().await;
+            let entries =
+                match models::Audit::find_specific_amount_after_id(feed.last_id, BATCH, &conn) {
+                    Ok(entries) => entries,
+                    Err(err) => {
+                        log::error!("Event stream stopped: {}", err);
+                        return None;
+                    }
+                };
+
+            let mut chunk = String::new();
+
+            for entry in &entries {
+                feed.last_id = entry.id;
+
+                match entry.visible(feed.access, &conn) {
+                    Ok(true) => chunk.push_str(&format_event(entry)),
+                    Ok(false) => {}
+                    Err(err) => {
+                        log::error!("Event stream stopped: {}", err);
+                        return None;
+                    }
+                }
+            }
+
+            chunk
+        };
+
+        if !chunk.is_empty() {
+            feed.idle = Duration::from_secs(0);
+            return Some((Ok(Bytes::from(chunk)), feed));
+        }
+
+        if feed.idle >= KEEP_ALIVE {
+            feed.idle = Duration::from_secs(0);
+            return Some((Ok(Bytes::from_static(b": keep-alive\n\n")), feed));
+        }
+
+        actix_rt::time::delay_for(feed.poll).await;
+        feed.idle += feed.poll;
+    }
+}
+
+pub async fn stream(
+    conn: ConnLock,
+    caller: Caller,
+    req: HttpRequest,
+    query: Query<StreamQuery>,
+) -> Result<impl Responder> {
+    caller.require(Permission::Read)?;
+
+    let last_id = match query.since.or_else(|| last_event_id(&req)) {
+        Some(id) => id,
+        None => models::Audit::last_id(&*conn.lock().await)?,
+    };
+    let poll = *crate::config::EVENTS_POLL_INTERVAL.lock().await;
+
+    let feed = Feed {
+        conn,
+        access: caller.access(),
+        last_id,
+        poll: Duration::from_millis(poll as u64),
+        idle: Duration::from_secs(0),
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .streaming(Box::pin(futures::stream::unfold(feed, next_events))))
+}
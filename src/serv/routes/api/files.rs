@@ -1,34 +1,10 @@
 use super::*;
 
-pub fn find_tags_by_names<S>(
-    names: &[S],
-    conn: &Connection,
-) -> std::result::Result<Vec<models::Tag>, ServiceError>
-where
-    S: serde::ser::Serialize + AsRef<str> + ToString,
-{
-    let tags = models::Tag::find_all_where_in_names(names, &conn)?;
-
-    if tags.len() != names.len() {
-        let mut hashset = std::collections::HashSet::new();
-
-        for tag in &tags {
-            hashset.insert(tag.name.as_ref());
-        }
-
-        let mut lost = Vec::with_capacity(names.len() - tags.len());
-
-        for tag in names {
-            if !hashset.contains(tag.as_ref()) {
-                lost.push(tag);
-            }
-        }
-
-        Err(service_error::consts::TAGS_NOT_FOUND
-            .clone()
-            .with_details(lost))
-    } else {
-        Ok(tags)
+/// Comma separated names of tags, empty list means no tags.
+pub fn split_tags(names: Option<&str>) -> Vec<&str> {
+    match names {
+        Some(names) if !names.is_empty() => names.split(',').collect(),
+        _ => Vec::new(),
     }
 }
 
@@ -36,97 +12,162 @@ where
 pub struct File {
     pub name: Box<str>,
     pub tags: Vec<Box<str>>,
+    pub shared: Option<bool>,
 }
 
-#[post("")]
-pub async fn create(conn: ConnLock, filej: web::Json<File>) -> Result<impl Responder> {
-    let filej = filej.0;
-    let mut conn = conn.lock().await;
-
-    if models::File::name_exists(&filej.name, &conn)? {
-        return Err(service_error::consts::FILE_DUPLICATION.clone());
-    }
-
-    let tags = find_tags_by_names(&filej.tags, &conn)?;
+pub async fn create(
+    conn: ConnLock,
+    caller: Caller,
+    cfg: web::Data<AppConfig>,
+    filej: Json<File>,
+) -> Result<impl Responder> {
+    caller.require(Permission::Tag)?;
 
-    let mut file = models::File::create_with_tags(
-        filej.name,
-        &tags.iter().map(|t| t.id).collect::<Box<[i32]>>(),
-        &mut conn,
-    )?;
-
-    file.tags = tags;
+    let mut conn = conn.lock().await;
 
-    res::json!(file)
+    res::json!(Library::new(&mut conn, caller.session())
+        .with_xattr(cfg.xattr)
+        .create_file(&filej.name, &filej.tags, filej.shared.unwrap_or(true))?)
 }
 
 // ---
 #[derive(Deserialize)]
 pub struct ListQuery {
     pub page: u16,
-    pub tags: Box<str>,
+    pub tags: Option<Box<str>>,
+    pub q: Option<Box<str>>,
     pub exact: Option<bool>,
+    pub sort: Option<models::search::Sort>,
+    pub facets: Option<bool>,
+}
+
+pub async fn list(
+    conn: ConnLock,
+    caller: Caller,
+    query: Query<ListQuery>,
+) -> Result<impl Responder> {
+    caller.require(Permission::Read)?;
+
+    let per_page = *crate::config::LIST_FILES_BY_TAG_PER_PAGE.lock().await;
+    let mut conn = conn.lock().await;
+
+    find_files(&query, per_page, &Library::new(&mut conn, caller.session()))
 }
 
-#[get("")]
-pub async fn list(conn: ConnLock, query: web::Query<ListQuery>) -> Result<impl Responder> {
-    let conn = conn.lock().await;
-    let tags = query.tags.split(',').collect::<Box<[_]>>();
-    let tags = find_tags_by_names(tags.as_ref(), &conn)?;
+pub fn find_files(query: &ListQuery, per_page: u32, library: &Library) -> Result<HttpResponse> {
+    let tags = split_tags(query.tags.as_deref());
+    let search = library::Query {
+        tags: &tags,
+        exact: query.exact.unwrap_or(false),
+        text: query.q.as_deref(),
+        sort: query.sort.unwrap_or_default(),
+    };
+
+    let files = library.files(&search, per_page, query.page as u32)?;
+
+    if query.facets == Some(true) {
+        let (total, facets) = library.facets(&search)?;
+
+        res::json!(tagz_types::FacetedList {
+            files: files.into_iter().map(Into::into).collect(),
+            total,
+            facets: facets.into_iter().map(Into::into).collect(),
+        })
+    } else {
+        res::json!(files)
+    }
+}
 
-    let ids = &tags.iter().map(|t| t.id).collect::<Box<[_]>>(); // FIXME: rusqlite: ToSql for Iterators ???
+//---
+pub async fn show(
+    conn: ConnLock,
+    caller: Caller,
+    filename: web::Path<Box<str>>,
+) -> Result<impl Responder> {
+    caller.require(Permission::Read)?;
 
-    let files = models::File::find_specific_amount_by_tags_ids_on_page(
-        &ids,
-        *crate::config::LIST_FILES_BY_TAG_PER_PAGE.lock().await,
-        query.page as u32,
-        query.exact.unwrap_or(false),
-        &conn,
-    )?;
+    let mut conn = conn.lock().await;
 
-    res::json!(files)
+    res::json!(Library::new(&mut conn, caller.session()).file(&unquote(&filename))?)
 }
 
 //---
-#[delete("{name}")]
-pub async fn delete(conn: ConnLock, filename: web::Path<Box<str>>) -> Result<impl Responder> {
-    let conn = conn.lock().await;
+pub async fn delete(
+    conn: ConnLock,
+    caller: Caller,
+    cfg: web::Data<AppConfig>,
+    filename: web::Path<Box<str>>,
+) -> Result<impl Responder> {
+    caller.require(Permission::Tag)?;
+
+    let mut conn = conn.lock().await;
 
-    let file = models::File::extract_from_name(filename.as_ref(), &conn)?;
-    file.unlink_all_tags(&conn)?;
-    file.delete(&conn)?;
+    Library::new(&mut conn, caller.session())
+        .with_xattr(cfg.xattr)
+        .delete_file(&unquote(&filename))?;
 
     res::no_content!()
 }
 
 //---
-#[delete("{name}")]
-pub async fn remove(conn: ConnLock, info: web::Path<(i32, Box<str>)>) -> Result<impl Responder> {
-    let conn = conn.lock().await;
-    let tag = models::Tag::extract_from_name(&info.1, &conn)?;
+pub async fn remove(
+    conn: ConnLock,
+    caller: Caller,
+    cfg: web::Data<AppConfig>,
+    info: web::Path<(i32, Box<str>)>,
+) -> Result<impl Responder> {
+    caller.require(Permission::Tag)?;
 
-    if models::relationships::file_id_and_tag_id_exists(info.0, tag.id, &conn)? {
-        models::relationships::delete_between_file_id_and_tag_id(info.0, tag.id, &conn)?;
+    let mut conn = conn.lock().await;
+    let (file_id, name) = info.into_inner();
 
-        res::no_content!()
-    } else {
-        Err(service_error::consts::REL_FILE_TAG_NOT_FOUND.clone())
-    }
+    Library::new(&mut conn, caller.session())
+        .with_xattr(cfg.xattr)
+        .remove_tag(file_id, &unquote(&name))?;
+
+    res::no_content!()
 }
 
 //---
-#[post("{name}")]
-pub async fn add(conn: ConnLock, info: web::Path<(i32, Box<str>)>) -> Result<impl Responder> {
-    let conn = conn.lock().await;
-    let tag = models::Tag::extract_from_name(&info.1, &conn)?;
+pub async fn add(
+    conn: ConnLock,
+    caller: Caller,
+    cfg: web::Data<AppConfig>,
+    info: web::Path<(i32, Box<str>)>,
+) -> Result<impl Responder> {
+    caller.require(Permission::Tag)?;
 
-    if models::relationships::file_id_and_tag_id_exists(info.0, tag.id, &conn)? {
-        Err(service_error::consts::REL_FILE_TAG_EXISTS.clone())
-    } else {
-        models::File::extract_id_exists(info.0, &conn)?;
+    let mut conn = conn.lock().await;
+    let (file_id, name) = info.into_inner();
 
-        models::relationships::FileTag::create(info.0, tag.id, &conn)?;
+    Library::new(&mut conn, caller.session())
+        .with_xattr(cfg.xattr)
+        .add_tag(file_id, &unquote(&name))?;
 
-        res::no_content!()
-    }
+    res::no_content!()
+}
+
+//---
+#[derive(Deserialize)]
+pub struct RelatedQuery {
+    pub limit: Option<u32>,
+}
+
+pub async fn related(
+    conn: ConnLock,
+    caller: Caller,
+    file_id: web::Path<i32>,
+    query: Query<RelatedQuery>,
+) -> Result<impl Responder> {
+    caller.require(Permission::Read)?;
+
+    let mut conn = conn.lock().await;
+    let limit = match query.limit {
+        Some(limit) => limit,
+        None => *crate::config::LIST_RELATED_TAGS_LIMIT.lock().await,
+    };
+
+    res::json!(
+        Library::new(&mut conn, caller.session()).related_to_file(file_id.into_inner(), limit)?
+    )
 }
@@ -1,7 +1,80 @@
 use super::*;
-use crate::models;
+use crate::{library, models, AppConfig, Library};
+use actix_web::{guard, http::Method};
 
+pub mod audit;
+pub mod collections;
+pub mod events;
 pub mod files;
+pub mod openapi;
+pub mod searches;
 pub mod tags;
+pub mod trash;
+pub mod webhooks;
 
 use tagz_cg_serv as res;
+
+/// Declares routes once, as [`ROUTES`] described by [`crate::serv::openapi`]
+/// and as [`configure`] registering them in the given order.
+macro_rules! routes {
+    ($($method:ident $path:literal => $handler:path,)*) => {
+        /// Method and path relative to `api/v1` of every route.
+        pub const ROUTES: &[(Method, &str)] = &[$((Method::$method, $path),)*];
+
+        pub fn configure(cfg: &mut web::ServiceConfig) {
+            $(cfg.service(
+                web::resource($path)
+                    .guard(guard::Method(Method::$method))
+                    .to($handler),
+            );)*
+        }
+    };
+}
+
+routes! {
+    GET "/openapi.json" => openapi::document,
+    GET "/audit" => audit::list,
+
+    GET "/tags/related" => tags::related,
+    GET "/tags/search" => tags::search,
+    POST "/tags" => tags::create,
+    PUT "/tags/{name}" => tags::rename,
+    DELETE "/tags/{name}" => tags::delete,
+    GET "/tags" => tags::list,
+
+    POST "/searches" => searches::create,
+    GET "/searches" => searches::list,
+    GET "/searches/{name}/files" => searches::evaluate,
+    GET "/searches/{name}" => searches::show,
+    PUT "/searches/{name}" => searches::update,
+    DELETE "/searches/{name}" => searches::delete,
+
+    POST "/collections" => collections::create,
+    GET "/collections" => collections::list,
+    GET "/collections/{name}" => collections::show,
+    PUT "/collections/{name}" => collections::rename,
+    DELETE "/collections/{name}" => collections::delete,
+    POST "/collections/{name}/files" => collections::add,
+    PUT "/collections/{name}/order" => collections::reorder,
+    DELETE "/collections/{name}/files/{file_id}" => collections::remove,
+    PUT "/collections/{name}/files/{file_id}" => collections::move_file,
+
+    GET "/events" => events::stream,
+
+    POST "/webhooks" => webhooks::create,
+    GET "/webhooks" => webhooks::list,
+    GET "/webhooks/{name}" => webhooks::show,
+    PUT "/webhooks/{name}" => webhooks::update,
+    DELETE "/webhooks/{name}" => webhooks::delete,
+
+    GET "/trash" => trash::list,
+    POST "/trash/{id}/restore" => trash::restore,
+
+    POST "/files" => files::create,
+    GET "/files/{name}" => files::show,
+    DELETE "/files/{name}" => files::delete,
+    GET "/files" => files::list,
+    GET "/files/{file_id}/related" => files::related,
+    POST "/files/{file_id}/{name}" => files::add,
+    DELETE "/files/{file_id}/{name}" => files::remove,
+}
@@ -0,0 +1,5 @@
+use super::*;
+
+pub async fn document() -> Result<impl Responder> {
+    Ok(HttpResponse::Ok().json(&*crate::serv::openapi::DOCUMENT))
+}
@@ -0,0 +1,131 @@
+use super::{files, *};
+
+#[derive(Deserialize)]
+pub struct Search {
+    pub name: Box<str>,
+    pub tags: Option<Box<str>>,
+    pub q: Option<Box<str>>,
+    pub sort: Option<models::search::Sort>,
+    pub exact: Option<bool>,
+    /// kept on update when absent
+    pub shared: Option<bool>,
+}
+
+impl Search {
+    fn query<'a>(&'a self, tags: &'a [&'a str]) -> library::Query<'a> {
+        library::Query {
+            tags,
+            exact: self.exact.unwrap_or(false),
+            text: self.q.as_deref(),
+            sort: self.sort.unwrap_or_default(),
+        }
+    }
+}
+
+pub async fn create(
+    conn: ConnLock,
+    caller: Caller,
+    searchj: Json<Search>,
+) -> Result<impl Responder> {
+    caller.require(Permission::Tag)?;
+
+    let mut conn = conn.lock().await;
+    let tags = files::split_tags(searchj.tags.as_deref());
+
+    res::json!(Library::new(&mut conn, caller.session()).create_search(
+        &searchj.name,
+        &searchj.query(&tags),
+        searchj.shared.unwrap_or(true)
+    )?)
+}
+
+//---
+pub async fn list(conn: ConnLock, caller: Caller) -> Result<impl Responder> {
+    caller.require(Permission::Read)?;
+
+    let mut conn = conn.lock().await;
+
+    res::json!(Library::new(&mut conn, caller.session()).searches()?)
+}
+
+//---
+pub async fn show(
+    conn: ConnLock,
+    caller: Caller,
+    name: web::Path<Box<str>>,
+) -> Result<impl Responder> {
+    caller.require(Permission::Read)?;
+
+    let mut conn = conn.lock().await;
+
+    res::json!(Library::new(&mut conn, caller.session()).search(&name)?)
+}
+
+//---
+pub async fn update(
+    conn: ConnLock,
+    caller: Caller,
+    name: web::Path<Box<str>>,
+    searchj: Json<Search>,
+) -> Result<impl Responder> {
+    caller.require(Permission::Tag)?;
+
+    let mut conn = conn.lock().await;
+    let tags = files::split_tags(searchj.tags.as_deref());
+
+    res::json!(Library::new(&mut conn, caller.session()).update_search(
+        &name,
+        &searchj.name,
+        &searchj.query(&tags),
+        searchj.shared
+    )?)
+}
+
+//---
+pub async fn delete(
+    conn: ConnLock,
+    caller: Caller,
+    name: web::Path<Box<str>>,
+) -> Result<impl Responder> {
+    caller.require(Permission::Tag)?;
+
+    let mut conn = conn.lock().await;
+
+    Library::new(&mut conn, caller.session()).delete_search(&name)?;
+
+    res::no_content!()
+}
+
+//---
+#[derive(Deserialize)]
+pub struct FilesQuery {
+    pub page: u16,
+    pub facets: Option<bool>,
+}
+
+pub async fn evaluate(
+    conn: ConnLock,
+    caller: Caller,
+    name: web::Path<Box<str>>,
+    query: Query<FilesQuery>,
+) -> Result<impl Responder> {
+    caller.require(Permission::Read)?;
+
+    let per_page = *crate::config::LIST_FILES_BY_TAG_PER_PAGE.lock().await;
+    let mut conn = conn.lock().await;
+    let library = Library::new(&mut conn, caller.session());
+    let search = library.search(&name)?;
+
+    files::find_files(
+        &files::ListQuery {
+            page: query.page,
+            tags: Some(search.tags.into()),
+            q: search.query.map(Into::into),
+            exact: Some(search.exact),
+            sort: Some(search.sort),
+            facets: query.facets,
+        },
+        per_page,
+        &library,
+    )
+}
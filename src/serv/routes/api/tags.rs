@@ -3,17 +3,20 @@ use super::*;
 #[derive(Deserialize)]
 pub struct Tag {
     pub name: Box<str>,
+    pub description: Option<Box<str>>,
+    pub shared: Option<bool>,
 }
 
-#[post("")]
-pub async fn create(conn: ConnLock, tagj: web::Json<Tag>) -> Result<impl Responder> {
-    let conn = conn.lock().await;
+pub async fn create(conn: ConnLock, caller: Caller, tagj: Json<Tag>) -> Result<impl Responder> {
+    caller.require(Permission::Tag)?;
 
-    if models::Tag::name_exists(tagj.name.as_ref(), &conn)? {
-        Err(service_error::consts::TAG_DUPLICATION.clone())
-    } else {
-        res::json!(models::Tag::create(tagj.name.as_ref(), &conn)?)
-    }
+    let mut conn = conn.lock().await;
+
+    res::json!(Library::new(&mut conn, caller.session()).create_tag(
+        &tagj.name,
+        tagj.description.as_deref(),
+        tagj.shared.unwrap_or(true)
+    )?)
 }
 
 // ---
@@ -22,40 +25,97 @@ pub struct DeleteQuery {
     pub confirm: Option<bool>,
 }
 
-#[delete("{name}")]
 pub async fn delete(
     conn: ConnLock,
-    query: web::Query<DeleteQuery>,
+    caller: Caller,
+    cfg: web::Data<AppConfig>,
+    query: Query<DeleteQuery>,
     name: web::Path<Box<str>>,
 ) -> Result<impl Responder> {
-    let conn = conn.lock().await;
+    caller.require(Permission::Tag)?;
+
+    let mut conn = conn.lock().await;
 
-    // get tag
-    let tag = models::Tag::extract_from_name(name.as_ref().as_ref(), &conn)?;
+    Library::new(&mut conn, caller.session())
+        .with_xattr(cfg.xattr)
+        .delete_tag(&unquote(&name), query.confirm == Some(true))?;
+
+    res::no_content!()
+}
 
-    // check if there are related files
-    let has_files = tag.has_related_files(&conn)?;
+//---
+#[derive(Deserialize)]
+pub struct Rename {
+    pub name: Box<str>,
+}
 
-    if has_files && query.confirm != Some(true) {
-        Err(service_error::consts::CONFIRMATION_REQUIRED.clone().with_message("Tag has related files, so all files with this tag will be unlinked. Confirm action by adding `?confirm=true` to query url."))
-    } else {
-        if has_files {
-            tag.unlink_all_files(&conn)?;
-        }
+pub async fn rename(
+    conn: ConnLock,
+    caller: Caller,
+    cfg: web::Data<AppConfig>,
+    name: web::Path<Box<str>>,
+    renamej: Json<Rename>,
+) -> Result<impl Responder> {
+    caller.require(Permission::Tag)?;
 
-        tag.delete(&conn)?;
+    let mut conn = conn.lock().await;
 
-        res::no_content!()
-    }
+    res::json!(Library::new(&mut conn, caller.session())
+        .with_xattr(cfg.xattr)
+        .rename_tag(&unquote(&name), &renamej.name)?)
 }
 
 //---
-#[get("")]
-pub async fn list(conn: ConnLock) -> Result<impl Responder> {
-    let conn = conn.lock().await;
+pub async fn list(conn: ConnLock, caller: Caller) -> Result<impl Responder> {
+    caller.require(Permission::Read)?;
+
+    let mut conn = conn.lock().await;
 
-    res::json!(models::Tag::all(&conn)?
+    res::json!(Library::new(&mut conn, caller.session())
+        .tags()?
         .iter()
         .map(|tag| &tag.name)
         .collect::<Box<[_]>>())
 }
+
+//---
+#[derive(Deserialize)]
+pub struct RelatedQuery {
+    pub tags: Box<str>,
+    pub limit: Option<u32>,
+}
+
+pub async fn related(
+    conn: ConnLock,
+    caller: Caller,
+    query: Query<RelatedQuery>,
+) -> Result<impl Responder> {
+    caller.require(Permission::Read)?;
+
+    let mut conn = conn.lock().await;
+    let tags = query.tags.split(',').collect::<Box<[_]>>();
+    let limit = match query.limit {
+        Some(limit) => limit,
+        None => *crate::config::LIST_RELATED_TAGS_LIMIT.lock().await,
+    };
+
+    res::json!(Library::new(&mut conn, caller.session()).related_tags(&tags, limit)?)
+}
+
+//---
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    pub q: Box<str>,
+}
+
+pub async fn search(
+    conn: ConnLock,
+    caller: Caller,
+    query: Query<SearchQuery>,
+) -> Result<impl Responder> {
+    caller.require(Permission::Read)?;
+
+    let mut conn = conn.lock().await;
+
+    res::json!(Library::new(&mut conn, caller.session()).search_tags(&query.q)?)
+}
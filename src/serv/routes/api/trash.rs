@@ -0,0 +1,38 @@
+use super::*;
+
+#[derive(Deserialize)]
+pub struct ListQuery {
+    pub page: u16,
+}
+
+pub async fn list(
+    conn: ConnLock,
+    caller: Caller,
+    query: Query<ListQuery>,
+) -> Result<impl Responder> {
+    caller.require(Permission::Read)?;
+
+    let per_page = *crate::config::LIST_TRASH_PER_PAGE.lock().await;
+    let mut conn = conn.lock().await;
+
+    res::json!(Library::new(&mut conn, caller.session()).trash(per_page, query.page as u32)?)
+}
+
+//---
+pub async fn restore(
+    conn: ConnLock,
+    caller: Caller,
+    cfg: web::Data<AppConfig>,
+    id: web::Path<i32>,
+) -> Result<impl Responder> {
+    caller.require(Permission::Tag)?;
+
+    let mut conn = conn.lock().await;
+    let mut library = Library::new(&mut conn, caller.session()).with_xattr(cfg.xattr);
+    let trash = library.restore(id.into_inner())?;
+
+    match trash.kind {
+        models::trash::Kind::File => res::json!(library.file(&trash.name)?),
+        models::trash::Kind::Tag => res::json!(library.tag(&trash.name)?),
+    }
+}
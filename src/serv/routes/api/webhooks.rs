@@ -0,0 +1,104 @@
+use super::{files, *};
+
+#[derive(Deserialize)]
+pub struct Webhook {
+    pub name: Box<str>,
+    pub url: Box<str>,
+    /// generated on creation when absent, kept on update
+    pub secret: Option<Box<str>>,
+    pub events: Option<Vec<models::audit::Action>>,
+    pub tags: Option<Box<str>>,
+}
+
+impl Webhook {
+    fn hook<'a>(&'a self, tags: &'a [&'a str]) -> library::Hook<'a> {
+        library::Hook {
+            name: &self.name,
+            url: &self.url,
+            secret: self.secret.as_deref(),
+            events: self.events.as_deref().unwrap_or_default(),
+            tags,
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+pub struct Created {
+    #[serde(flatten)]
+    pub webhook: models::Webhook,
+    /// shown only once
+    pub secret: String,
+}
+
+/// Webhooks receive entries of the whole audit log, so admins only.
+pub async fn create(
+    conn: ConnLock,
+    caller: Caller,
+    hookj: Json<Webhook>,
+) -> Result<impl Responder> {
+    caller.require(Permission::Admin)?;
+
+    let mut conn = conn.lock().await;
+    let tags = files::split_tags(hookj.tags.as_deref());
+    let webhook = Library::new(&mut conn, caller.session()).create_webhook(&hookj.hook(&tags))?;
+
+    res::json!(
+        CREATED,
+        Created {
+            secret: webhook.secret.clone(),
+            webhook,
+        }
+    )
+}
+
+//---
+pub async fn list(conn: ConnLock, caller: Caller) -> Result<impl Responder> {
+    caller.require(Permission::Admin)?;
+
+    let mut conn = conn.lock().await;
+
+    res::json!(Library::new(&mut conn, caller.session()).webhooks()?)
+}
+
+//---
+pub async fn show(
+    conn: ConnLock,
+    caller: Caller,
+    name: web::Path<Box<str>>,
+) -> Result<impl Responder> {
+    caller.require(Permission::Admin)?;
+
+    let mut conn = conn.lock().await;
+
+    res::json!(Library::new(&mut conn, caller.session()).webhook(&name)?)
+}
+
+//---
+pub async fn update(
+    conn: ConnLock,
+    caller: Caller,
+    name: web::Path<Box<str>>,
+    hookj: Json<Webhook>,
+) -> Result<impl Responder> {
+    caller.require(Permission::Admin)?;
+
+    let mut conn = conn.lock().await;
+    let tags = files::split_tags(hookj.tags.as_deref());
+
+    res::json!(Library::new(&mut conn, caller.session()).update_webhook(&name, &hookj.hook(&tags))?)
+}
+
+//---
+pub async fn delete(
+    conn: ConnLock,
+    caller: Caller,
+    name: web::Path<Box<str>>,
+) -> Result<impl Responder> {
+    caller.require(Permission::Admin)?;
+
+    let mut conn = conn.lock().await;
+
+    Library::new(&mut conn, caller.session()).delete_webhook(&name)?;
+
+    res::no_content!()
+}
@@ -1,6 +1,10 @@
-use super::{service_error, ServiceError};
-use crate::Connection;
-use actix_web::{delete, get, post, web, Responder};
+use super::{
+    auth::Caller,
+    extract::{Json, Query},
+    ServiceError,
+};
+use crate::{models::Permission, Connection};
+use actix_web::{web, HttpResponse, Responder};
 use futures::lock::Mutex;
 use serde::Deserialize;
 
@@ -8,3 +12,36 @@ pub mod api;
 
 pub type ConnLock = web::Data<Mutex<Connection>>;
 pub type Result<T> = std::result::Result<T, ServiceError>;
+
+/// Decodes every `%XX` escape of a path segment in a single pass. Actix
+/// decodes the rest itself but keeps `%2F`, `%2B` and `%25`, so names like
+/// `/home/cat.png` or `c++` come here escaped. Invalid escapes and segments
+/// not decoding to UTF-8 are kept as they are.
+pub fn unquote(segment: &str) -> String {
+    let bytes = segment.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let decoded = if bytes[i] == b'%' && i + 2 < bytes.len() {
+            std::str::from_utf8(&bytes[i + 1..i + 3])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        } else {
+            None
+        };
+
+        match decoded {
+            Some(byte) => {
+                out.push(byte);
+                i += 3;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8(out).unwrap_or_else(|_| segment.to_owned())
+}
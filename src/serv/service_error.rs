@@ -1,88 +1,183 @@
-use actix_web::{dev::Body, http::StatusCode, HttpResponse, ResponseError};
-use rusqlite::Error as SqlError;
-use serde::ser::Serialize;
-use serde_json::value::Value;
-use std::{
-    borrow::Cow,
-    fmt::{Debug, Display, Formatter, Result as FmtResult},
+//! Errors of the API, answered as `{"Err": {"status", "message", "details"}}`.
+//!
+//! `status` is a stable [`ErrorCode`] clients may match on, `message` is
+//! localized by [`super::i18n::Localization`] and must not be parsed.
+
+use super::i18n::Lang;
+use crate::library::Error as LibraryError;
+use actix_web::{
+    dev::Body,
+    error::{JsonPayloadError, PathError},
+    http::StatusCode,
+    HttpRequest, HttpResponse, ResponseError,
 };
+use rusqlite::{ffi, Error as SqlError};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use serde_json::value::Value;
+use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
 
-pub mod consts {
-    use super::*;
+macro_rules! error_codes {
+    ($($(#[$meta:meta])* $code:ident($name:literal, $status:ident) en: $en:literal, ru: $ru:literal;)*) => {
+        /// Every error the API reports, see [`ErrorCode::http_status`] for HTTP mapping.
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum ErrorCode {
+            $($(#[$meta])* $code,)*
+        }
 
-    lazy_static! {
-        pub static ref TAGS_NOT_FOUND: ServiceError = ServiceError::bad_request(
-            "TAGS_NOT_FOUND",
-            "Could not found some tags to create file. Check details to get list of unknown tags.",
-        );
+        impl ErrorCode {
+            pub const ALL: &'static [ErrorCode] = &[$(ErrorCode::$code,)*];
 
-        pub static ref FILE_DUPLICATION: ServiceError = ServiceError::bad_request(
-            "FILE_DUPLICATION",
-            "File with specified name already exists. You must edit or delete it instead of recreating."
-        );
+            /// Stable name sent as `status`.
+            pub fn as_str(self) -> &'static str {
+                match self {
+                    $(ErrorCode::$code => $name,)*
+                }
+            }
 
-        pub static ref REL_FILE_TAG_NOT_FOUND: ServiceError = ServiceError::not_found(
-            "REL_FILE_TAG_NOT_FOUND",
-            "File does not have specified tag or does not exist.",
-        );
+            pub fn http_status(self) -> StatusCode {
+                match self {
+                    $(ErrorCode::$code => StatusCode::$status,)*
+                }
+            }
 
-        pub static ref REL_FILE_TAG_EXISTS: ServiceError = ServiceError::bad_request(
-            "REL_FILE_TAG_EXISTS",
-            "File already has specified tag.",
-        );
+            pub fn message(self, lang: Lang) -> &'static str {
+                match (self, lang) {
+                    $(
+                        (ErrorCode::$code, Lang::En) => $en,
+                        (ErrorCode::$code, Lang::Ru) => $ru,
+                    )*
+                }
+            }
+        }
+    };
+}
 
-        pub static ref TAG_DUPLICATION: ServiceError = ServiceError::bad_request(
-            "TAG_DUPLICATION",
-            "Tag with the given name already exists.",
-        );
+error_codes! {
+    //--- 400, request cannot be done as it is
+    /// details have `reason` and `field`, the path of the value not fitting
+    /// like `files[0]`, or `line` and `column` when the body is not JSON at all
+    InvalidJson("INVALID_JSON", BAD_REQUEST)
+        en: "Request body is not valid JSON of expected shape. Check details.",
+        ru: "Тело запроса не является корректным JSON ожидаемого вида. Подробности указаны в деталях.";
+    /// details have `reason` and `field`, the parameter not fitting
+    InvalidQuery("INVALID_QUERY", BAD_REQUEST)
+        en: "Query parameters are missing or malformed. Check details.",
+        ru: "Параметры запроса отсутствуют или некорректны. Подробности указаны в деталях.";
+    /// details have `reason` and `field`, the segment whose value cannot be
+    /// parsed
+    InvalidPath("INVALID_PATH", BAD_REQUEST)
+        en: "Path segment is malformed. Check details.",
+        ru: "Сегмент пути некорректен. Подробности указаны в деталях.";
+    /// details list unknown tags
+    TagsNotFound("TAGS_NOT_FOUND", BAD_REQUEST)
+        en: "Could not find some tags. Check details to get list of unknown tags.",
+        ru: "Некоторые теги не найдены. Список неизвестных тегов указан в деталях.";
+    ConfirmationRequired("CONFIRMATION_REQUIRED", BAD_REQUEST)
+        en: "Tag has related files, so all files with this tag will be unlinked. Confirm action by adding `?confirm=true` to query url.",
+        ru: "У тега есть файлы, все они потеряют этот тег. Подтвердите действие, добавив `?confirm=true` к адресу запроса.";
+    CollectionOrderMismatch("COLLECTION_ORDER_MISMATCH", BAD_REQUEST)
+        en: "New order must list every file of the collection exactly once.",
+        ru: "Новый порядок должен перечислять каждый файл коллекции ровно один раз.";
+    WebhookInvalidUrl("WEBHOOK_INVALID_URL", BAD_REQUEST)
+        en: "Webhook url must start with `http://` or `https://`.",
+        ru: "Адрес вебхука должен начинаться с `http://` или `https://`.";
 
-        pub static ref CONFIRMATION_REQUIRED: ServiceError = ServiceError::bad_request(
-            "CONFIRMATION_REQUIRED",
-            ""
-        );
-    }
-}
+    //--- 401 and 403, caller is not allowed
+    Unauthorized("UNAUTHORIZED", UNAUTHORIZED)
+        en: "Valid API token is required. Pass it as `Authorization: Bearer <token>` header.",
+        ru: "Требуется действующий токен API. Передайте его в заголовке `Authorization: Bearer <token>`.";
+    PermissionDenied("PERMISSION_DENIED", FORBIDDEN)
+        en: "Caller is not permitted to perform this action.",
+        ru: "Недостаточно прав для этого действия.";
+    ReadOnly("READ_ONLY", FORBIDDEN)
+        en: "Server is running in read-only mode.",
+        ru: "Сервер работает в режиме только для чтения.";
+    InsufficientScope("INSUFFICIENT_SCOPE", FORBIDDEN)
+        en: "Token scope does not allow this action.",
+        ru: "Область действия токена не позволяет это действие.";
 
-#[derive(Clone, serde::Serialize, Debug)]
-pub struct ServiceError {
-    #[serde(skip)]
-    status_code: StatusCode,
+    //--- 413 and 415, body cannot be read
+    PayloadTooLarge("PAYLOAD_TOO_LARGE", PAYLOAD_TOO_LARGE)
+        en: "Request body is too large.",
+        ru: "Тело запроса слишком велико.";
+    UnsupportedMediaType("UNSUPPORTED_MEDIA_TYPE", UNSUPPORTED_MEDIA_TYPE)
+        en: "Request body must be sent as `Content-Type: application/json`.",
+        ru: "Тело запроса должно передаваться с `Content-Type: application/json`.";
 
-    status: &'static str,
-    message: Cow<'static, str>,
+    //--- 404, entry is missing or invisible to the caller
+    FileNotFound("FILE_NOT_FOUND", NOT_FOUND)
+        en: "Specified file cannot be found.",
+        ru: "Указанный файл не найден.";
+    TagNotFound("TAG_NOT_FOUND", NOT_FOUND)
+        en: "Specified tag cannot be found.",
+        ru: "Указанный тег не найден.";
+    SearchNotFound("SEARCH_NOT_FOUND", NOT_FOUND)
+        en: "Specified saved search cannot be found.",
+        ru: "Указанный сохранённый поиск не найден.";
+    CollectionNotFound("COLLECTION_NOT_FOUND", NOT_FOUND)
+        en: "Specified collection cannot be found.",
+        ru: "Указанная коллекция не найдена.";
+    WebhookNotFound("WEBHOOK_NOT_FOUND", NOT_FOUND)
+        en: "Specified webhook cannot be found.",
+        ru: "Указанный вебхук не найден.";
+    TrashNotFound("TRASH_NOT_FOUND", NOT_FOUND)
+        en: "Specified trash entry cannot be found.",
+        ru: "Указанная запись корзины не найдена.";
+    RelFileTagNotFound("REL_FILE_TAG_NOT_FOUND", NOT_FOUND)
+        en: "File does not have specified tag or does not exist.",
+        ru: "У файла нет указанного тега, или файл не существует.";
+    RelCollectionFileNotFound("REL_COLLECTION_FILE_NOT_FOUND", NOT_FOUND)
+        en: "Collection does not contain specified file.",
+        ru: "Коллекция не содержит указанный файл.";
 
-    details: Option<Value>, // IDEA: maybe Box<dyn Serialize> ?
-}
+    //--- 409, entry conflicts with an existing one
+    FileDuplication("FILE_DUPLICATION", CONFLICT)
+        en: "File with specified name already exists. You must edit or delete it instead of recreating.",
+        ru: "Файл с таким именем уже существует. Измените или удалите его вместо повторного создания.";
+    TagDuplication("TAG_DUPLICATION", CONFLICT)
+        en: "Tag with the given name already exists.",
+        ru: "Тег с таким именем уже существует.";
+    SearchDuplication("SEARCH_DUPLICATION", CONFLICT)
+        en: "Saved search with the given name already exists.",
+        ru: "Сохранённый поиск с таким именем уже существует.";
+    CollectionDuplication("COLLECTION_DUPLICATION", CONFLICT)
+        en: "Collection with the given name already exists.",
+        ru: "Коллекция с таким именем уже существует.";
+    WebhookDuplication("WEBHOOK_DUPLICATION", CONFLICT)
+        en: "Webhook with the given name already exists.",
+        ru: "Вебхук с таким именем уже существует.";
+    RelFileTagExists("REL_FILE_TAG_EXISTS", CONFLICT)
+        en: "File already has specified tag.",
+        ru: "У файла уже есть указанный тег.";
+    RelCollectionFileExists("REL_COLLECTION_FILE_EXISTS", CONFLICT)
+        en: "Collection already contains specified file.",
+        ru: "Коллекция уже содержит указанный файл.";
+    /// unique constraint violated by a change racing with another one,
+    /// details name the constraint; also a name held by an entry invisible
+    /// to the caller, without details
+    Conflict("CONFLICT", CONFLICT)
+        en: "Entry conflicts with an existing one.",
+        ru: "Запись конфликтует с существующей.";
 
-impl ServiceError {
-    pub fn with_message<C>(mut self, message: C) -> Self
-    where
-        C: Into<Cow<'static, str>>,
-    {
-        self.message = message.into();
-        self
-    }
+    //--- 500
+    /// database failed, the cause is logged and not sent
+    Sql("SQL", INTERNAL_SERVER_ERROR)
+        en: "Database error occurred.",
+        ru: "Произошла ошибка базы данных.";
+}
 
-    pub fn bad_request<C>(status: &'static str, message: C) -> Self
-    where
-        C: Into<Cow<'static, str>>,
-    {
-        Self {
-            status_code: StatusCode::BAD_REQUEST,
-            status,
-            message: message.into(),
-            details: None,
-        }
-    }
+#[derive(Clone, Debug)]
+pub struct ServiceError {
+    pub code: ErrorCode,
+    pub lang: Lang,
+    details: Option<Value>,
+}
 
-    pub fn not_found<C>(status: &'static str, message: C) -> Self
-    where
-        C: Into<Cow<'static, str>>,
-    {
+impl ServiceError {
+    pub fn new(code: ErrorCode) -> Self {
         Self {
-            status_code: StatusCode::NOT_FOUND,
-            status,
-            message: message.into(),
+            code,
+            lang: Lang::En,
             details: None,
         }
     }
@@ -94,6 +189,31 @@ impl ServiceError {
         self.details = Some(serde_json::json!(details));
         self
     }
+
+    pub fn localized(mut self, lang: Lang) -> Self {
+        self.lang = lang;
+        self
+    }
+}
+
+impl From<ErrorCode> for ServiceError {
+    #[inline]
+    fn from(code: ErrorCode) -> Self {
+        Self::new(code)
+    }
+}
+
+impl Serialize for ServiceError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("ServiceError", 3)?;
+        state.serialize_field("status", self.code.as_str())?;
+        state.serialize_field("message", self.code.message(self.lang))?;
+        state.serialize_field("details", &self.details)?;
+        state.end()
+    }
 }
 
 impl Display for ServiceError {
@@ -105,19 +225,157 @@ impl Display for ServiceError {
 
 impl From<SqlError> for ServiceError {
     fn from(err: SqlError) -> Self {
-        Self {
-            status_code: StatusCode::INTERNAL_SERVER_ERROR,
-            status: "SQL",
-            message: Cow::Owned(format!("{}", err)),
-            details: None,
+        match err {
+            SqlError::SqliteFailure(
+                ffi::Error {
+                    extended_code: ffi::SQLITE_CONSTRAINT_UNIQUE,
+                    ..
+                },
+                ref message,
+            )
+            | SqlError::SqliteFailure(
+                ffi::Error {
+                    extended_code: ffi::SQLITE_CONSTRAINT_PRIMARYKEY,
+                    ..
+                },
+                ref message,
+            ) => {
+                // `UNIQUE constraint failed: tags.name`
+                let constraint = message
+                    .as_deref()
+                    .and_then(|message| message.splitn(2, ": ").nth(1));
+
+                ServiceError::new(ErrorCode::Conflict)
+                    .with_details(serde_json::json!({ "constraint": constraint }))
+            }
+            err => {
+                log::error!("SQL error: {}", err);
+
+                ServiceError::new(ErrorCode::Sql)
+            }
         }
     }
 }
 
+impl From<LibraryError> for ServiceError {
+    fn from(err: LibraryError) -> Self {
+        match err {
+            LibraryError::FileNotFound => ErrorCode::FileNotFound.into(),
+            LibraryError::TagNotFound => ErrorCode::TagNotFound.into(),
+            LibraryError::TagsNotFound(names) => {
+                ServiceError::new(ErrorCode::TagsNotFound).with_details(names)
+            }
+            LibraryError::FileDuplication => ErrorCode::FileDuplication.into(),
+            LibraryError::TagDuplication => ErrorCode::TagDuplication.into(),
+            LibraryError::RelationNotFound => ErrorCode::RelFileTagNotFound.into(),
+            LibraryError::RelationExists => ErrorCode::RelFileTagExists.into(),
+            LibraryError::ConfirmationRequired => ErrorCode::ConfirmationRequired.into(),
+            LibraryError::PermissionDenied => ErrorCode::PermissionDenied.into(),
+            LibraryError::NameTaken => ErrorCode::Conflict.into(),
+            LibraryError::CollectionNotFound => ErrorCode::CollectionNotFound.into(),
+            LibraryError::CollectionDuplication => ErrorCode::CollectionDuplication.into(),
+            LibraryError::CollectionFileNotFound => ErrorCode::RelCollectionFileNotFound.into(),
+            LibraryError::CollectionFileExists => ErrorCode::RelCollectionFileExists.into(),
+            LibraryError::CollectionOrderMismatch => ErrorCode::CollectionOrderMismatch.into(),
+            LibraryError::SearchNotFound => ErrorCode::SearchNotFound.into(),
+            LibraryError::SearchDuplication => ErrorCode::SearchDuplication.into(),
+            LibraryError::TrashNotFound => ErrorCode::TrashNotFound.into(),
+            LibraryError::WebhookNotFound => ErrorCode::WebhookNotFound.into(),
+            LibraryError::WebhookDuplication => ErrorCode::WebhookDuplication.into(),
+            LibraryError::WebhookInvalidUrl => ErrorCode::WebhookInvalidUrl.into(),
+            LibraryError::Sql(err) => err.into(),
+        }
+    }
+}
+
+impl From<JsonPayloadError> for ServiceError {
+    fn from(err: JsonPayloadError) -> Self {
+        match err {
+            JsonPayloadError::Overflow => ErrorCode::PayloadTooLarge.into(),
+            JsonPayloadError::ContentType => ErrorCode::UnsupportedMediaType.into(),
+            JsonPayloadError::Deserialize(err) => {
+                let reason = err.to_string();
+
+                ServiceError::new(ErrorCode::InvalidJson).with_details(serde_json::json!({
+                    "field": field(&reason),
+                    "line": err.line(),
+                    "column": err.column(),
+                    "reason": reason,
+                }))
+            }
+            JsonPayloadError::Payload(err) => ServiceError::new(ErrorCode::InvalidJson)
+                .with_details(serde_json::json!({ "reason": err.to_string() })),
+        }
+    }
+}
+
+/// Value of the body not fitting the expected shape, see [`super::extract::Json`].
+impl From<serde_path_to_error::Error<serde_json::Error>> for ServiceError {
+    fn from(err: serde_path_to_error::Error<serde_json::Error>) -> Self {
+        let reason = err.inner().to_string();
+
+        ServiceError::new(ErrorCode::InvalidJson).with_details(serde_json::json!({
+            "field": path_field(err.path(), &reason),
+            "reason": reason,
+        }))
+    }
+}
+
+/// See [`super::extract::Query`].
+impl From<serde_path_to_error::Error<serde_urlencoded::de::Error>> for ServiceError {
+    fn from(err: serde_path_to_error::Error<serde_urlencoded::de::Error>) -> Self {
+        let reason = err.inner().to_string();
+
+        ServiceError::new(ErrorCode::InvalidQuery).with_details(serde_json::json!({
+            "field": path_field(err.path(), &reason),
+            "reason": reason,
+        }))
+    }
+}
+
+/// Handler of `web::PathConfig`, the field is the segment whose value the
+/// reason quotes, like `{file_id}` of `can not parse "abc" to a i32`.
+pub fn path_error(err: PathError, req: &HttpRequest) -> actix_web::Error {
+    let PathError::Deserialize(err) = err;
+    let reason = err.to_string();
+    let field = field(&reason).or_else(|| {
+        req.match_info()
+            .iter()
+            .find(|(_, value)| reason.contains(&format!("\"{}\"", value)))
+            .map(|(name, _)| name)
+    });
+
+    ServiceError::new(ErrorCode::InvalidPath)
+        .with_details(serde_json::json!({
+            "field": field,
+            "reason": reason,
+        }))
+        .into()
+}
+
+/// Path of the value serde failed on, like `files[0]`, followed by the field
+/// named by messages about missing, unknown and duplicate fields.
+fn path_field(path: &serde_path_to_error::Path, reason: &str) -> Option<String> {
+    let path = Some(path.to_string()).filter(|path| path != ".");
+
+    match (path, field(reason)) {
+        (Some(path), Some(field)) => Some(format!("{}.{}", path, field)),
+        (path, field) => path.or_else(|| field.map(ToOwned::to_owned)),
+    }
+}
+
+/// Field named by serde messages like ``missing field `page` ``.
+fn field(reason: &str) -> Option<&str> {
+    let start = reason.find("field `")? + 7;
+    let len = reason[start..].find('`')?;
+
+    Some(&reason[start..start + len])
+}
+
 impl ResponseError for ServiceError {
     #[inline]
     fn status_code(&self) -> StatusCode {
-        self.status_code
+        self.code.http_status()
     }
 
     fn error_response(&self) -> HttpResponse<Body> {
@@ -127,6 +385,116 @@ impl ResponseError for ServiceError {
             err: &'a ServiceError,
         }
 
-        HttpResponse::build(self.status_code).json(ErrorBody { err: self })
+        HttpResponse::build(self.status_code()).json(ErrorBody { err: self })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serv::extract::{Json, Query};
+    use actix_web::{test, web, App};
+
+    #[allow(dead_code)]
+    #[derive(serde::Deserialize)]
+    struct Page {
+        page: u32,
+        #[serde(default)]
+        files: Vec<i32>,
+    }
+
+    async fn details(uri: &str, json: Option<Value>) -> Value {
+        let mut app = test::init_service(
+            App::new()
+                .app_data(
+                    web::JsonConfig::default()
+                        .error_handler(|err, _| ServiceError::from(err).into()),
+                )
+                .app_data(web::PathConfig::default().error_handler(path_error))
+                .route("/json", web::post().to(|_: Json<Page>| HttpResponse::Ok()))
+                .route("/query", web::get().to(|_: Query<Page>| HttpResponse::Ok()))
+                .route(
+                    "/path/{file_id}",
+                    web::get().to(|_: web::Path<(i32,)>| HttpResponse::Ok()),
+                ),
+        )
+        .await;
+        let req = match json {
+            Some(json) => test::TestRequest::post().uri(uri).set_json(&json),
+            None => test::TestRequest::get().uri(uri),
+        };
+        let res = test::call_service(&mut app, req.to_request()).await;
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let body: Value = test::read_body_json(res).await;
+
+        body["Err"]["details"].clone()
+    }
+
+    async fn json_field(json: Value) -> Value {
+        details("/json", Some(json)).await["field"].clone()
+    }
+
+    #[actix_rt::test]
+    async fn json_error_names_field() {
+        assert_eq!(json_field(serde_json::json!({})).await, "page");
+        assert_eq!(
+            json_field(serde_json::json!({ "page": "one" })).await,
+            "page"
+        );
+        assert_eq!(json_field(serde_json::json!({ "page": -1 })).await, "page");
+        assert_eq!(
+            json_field(serde_json::json!({ "page": 1, "files": ["x"] })).await,
+            "files[0]"
+        );
+
+        let mistyped = details("/json", Some(serde_json::json!({ "page": "one" }))).await;
+
+        assert!(mistyped["reason"]
+            .as_str()
+            .unwrap()
+            .contains("invalid type"));
+    }
+
+    #[actix_rt::test]
+    async fn json_syntax_error_has_position() {
+        let mut app = test::init_service(
+            App::new()
+                .app_data(
+                    web::JsonConfig::default()
+                        .error_handler(|err, _| ServiceError::from(err).into()),
+                )
+                .route("/json", web::post().to(|_: Json<Page>| HttpResponse::Ok())),
+        )
+        .await;
+        let req = test::TestRequest::post()
+            .uri("/json")
+            .header("Content-Type", "application/json")
+            .set_payload("{\"page\":")
+            .to_request();
+        let res = test::call_service(&mut app, req).await;
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let body: Value = test::read_body_json(res).await;
+
+        assert_eq!(body["Err"]["status"], "INVALID_JSON");
+        assert_eq!(body["Err"]["details"]["line"], 1);
+    }
+
+    #[actix_rt::test]
+    async fn query_error_names_field() {
+        assert_eq!(details("/query", None).await["field"], "page");
+        assert_eq!(details("/query?page=one", None).await["field"], "page");
+        assert_eq!(details("/query?page=1&page=2", None).await["field"], "page");
+    }
+
+    #[actix_rt::test]
+    async fn path_error_names_segment() {
+        let details = details("/path/abc", None).await;
+
+        assert_eq!(details["field"], "file_id");
+        assert!(details["reason"].as_str().unwrap().contains("\"abc\""));
     }
 }
@@ -0,0 +1,106 @@
+//! TLS via rustls with certificate reload on `SIGHUP` where there are signals,
+//! elsewhere the daemon must be restarted to pick up a new certificate.
+//!
+//! For local testing a self-signed pair can be made with
+//! `openssl req -x509 -newkey rsa:2048 -nodes -keyout key.pem -out cert.pem -subj /CN=localhost`
+//! and the daemon started with `--tls-cert cert.pem --tls-key key.pem`.
+
+use rustls::{
+    internal::pemfile,
+    sign::{self, CertifiedKey},
+    ClientHello, NoClientAuth, ResolvesServerCert, ServerConfig,
+};
+use std::{
+    fs::File,
+    io::{BufReader, Error, ErrorKind, Result},
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+pub struct ReloadableCert {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    key: RwLock<CertifiedKey>,
+}
+
+impl ReloadableCert {
+    pub fn load(cert_path: &Path, key_path: &Path) -> Result<Self> {
+        Ok(Self {
+            key: RwLock::new(load_certified_key(cert_path, key_path)?),
+            cert_path: cert_path.to_owned(),
+            key_path: key_path.to_owned(),
+        })
+    }
+
+    /// Keeps serving previous certificate if new one cannot be loaded.
+    pub fn reload(&self) -> Result<()> {
+        let key = load_certified_key(&self.cert_path, &self.key_path)?;
+        *self.key.write().unwrap() = key;
+
+        Ok(())
+    }
+}
+
+impl ResolvesServerCert for ReloadableCert {
+    fn resolve(&self, _: ClientHello) -> Option<CertifiedKey> {
+        Some(self.key.read().unwrap().clone())
+    }
+}
+
+pub fn server_config(cert: Arc<ReloadableCert>) -> ServerConfig {
+    let mut config = ServerConfig::new(NoClientAuth::new());
+    config.cert_resolver = cert;
+
+    config
+}
+
+/// Must be called within actix system.
+#[cfg(unix)]
+pub fn reload_on_sighup(cert: Arc<ReloadableCert>) -> Result<()> {
+    use actix_rt::signal::unix::{signal, SignalKind};
+
+    let mut hangup = signal(SignalKind::hangup())?;
+
+    actix_rt::spawn(async move {
+        while hangup.recv().await.is_some() {
+            match cert.reload() {
+                Ok(()) => log::info!("TLS certificate reloaded."),
+                Err(err) => log::error!("Could not reload TLS certificate: {}", err),
+            }
+        }
+    });
+
+    Ok(())
+}
+
+fn load_certified_key(cert_path: &Path, key_path: &Path) -> Result<CertifiedKey> {
+    let invalid = |what: &str, path: &Path| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("no valid {} found in {}", what, path.display()),
+        )
+    };
+
+    let certs = pemfile::certs(&mut BufReader::new(File::open(cert_path)?))
+        .map_err(|_| invalid("certificates", cert_path))?;
+
+    if certs.is_empty() {
+        return Err(invalid("certificates", cert_path));
+    }
+
+    let mut keys = pemfile::pkcs8_private_keys(&mut BufReader::new(File::open(key_path)?))
+        .map_err(|_| invalid("private key", key_path))?;
+
+    if keys.is_empty() {
+        keys = pemfile::rsa_private_keys(&mut BufReader::new(File::open(key_path)?))
+            .map_err(|_| invalid("private key", key_path))?;
+    }
+
+    let key = keys
+        .first()
+        .ok_or_else(|| invalid("private key", key_path))?;
+    let signing_key =
+        sign::any_supported_type(key).map_err(|_| invalid("private key", key_path))?;
+
+    Ok(CertifiedKey::new(certs, Arc::new(signing_key)))
+}
@@ -0,0 +1,56 @@
+//! Unix domain socket listener, selected by `unix:/path/to/sock` target.
+
+use std::{
+    fs,
+    io::{Error, ErrorKind, Result},
+    os::unix::net::UnixStream,
+    path::Path,
+};
+
+pub const PREFIX: &str = "unix:";
+
+pub fn socket_path(target: &str) -> Option<&Path> {
+    target.strip_prefix(PREFIX).map(Path::new)
+}
+
+/// Removes socket left by a previous run, refuses to touch one still served
+/// by another process or a path which is not a socket.
+pub fn remove_stale(path: &Path) -> Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(ref err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+
+    if !metadata.file_type().is_socket() {
+        return Err(Error::new(
+            ErrorKind::AlreadyExists,
+            format!("{} exists and is not a socket", path.display()),
+        ));
+    }
+
+    if UnixStream::connect(path).is_ok() {
+        return Err(Error::new(
+            ErrorKind::AddrInUse,
+            format!("{} is in use by another process", path.display()),
+        ));
+    }
+
+    log::info!("Removing stale socket {}.", path.display());
+
+    fs::remove_file(path)
+}
+
+/// Runs `bind` with umask letting created sockets get exactly `mode`, so the
+/// socket never exists with wider permissions. Umask is process-wide, bind
+/// before workers start.
+pub fn with_mode<T>(mode: u32, bind: impl FnOnce() -> T) -> T {
+    let mask = !mode as libc::mode_t & 0o777;
+    let previous = unsafe { libc::umask(mask) };
+    let res = bind();
+    unsafe { libc::umask(previous) };
+
+    res
+}
@@ -0,0 +1,254 @@
+//! Delivery of audit entries to webhooks.
+//!
+//! Every entry is `POST`ed as JSON with headers `X-Tagz-Event` (action),
+//! `X-Tagz-Delivery` (audit id) and `X-Tagz-Signature: sha256=<hex>`, being
+//! HMAC-SHA256 of the body keyed with the webhook secret. Every webhook is
+//! served by its own task getting entries in order, so a slow receiver delays
+//! no other. A failed entry is retried with exponential backoff and skipped
+//! after [`models::webhook::MAX_ATTEMPTS`].
+
+use crate::{models, Connection};
+use actix_web::{client::Client, web::Data};
+use futures::lock::Mutex;
+use std::{cell::RefCell, collections::HashSet, rc::Rc, time::Duration};
+
+const BATCH: u32 = 100;
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Must be spawned within actix system.
+pub async fn deliver_forever(connection: Data<Mutex<Connection>>) {
+    let client = Client::default();
+    // webhooks whose task is still delivering
+    let busy = Rc::new(RefCell::new(HashSet::new()));
+    let mut interval = actix_rt::time::interval(Duration::from_secs(1));
+
+    loop {
+        interval.tick().await;
+
+        let webhooks = match models::Webhook::all_due(&*connection.lock().await) {
+            Ok(webhooks) => webhooks,
+            Err(err) => {
+                log::error!("Could not load webhooks: {}", err);
+                continue;
+            }
+        };
+
+        for webhook in webhooks {
+            if !busy.borrow_mut().insert(webhook.id) {
+                continue;
+            }
+
+            let client = client.clone();
+            let connection = Data::clone(&connection);
+            let busy = Rc::clone(&busy);
+
+            actix_rt::spawn(async move {
+                let id = webhook.id;
+
+                if let Err(err) = deliver(&client, &connection, webhook).await {
+                    log::error!("Could not deliver webhook: {}", err);
+                }
+
+                busy.borrow_mut().remove(&id);
+            });
+        }
+    }
+}
+
+/// Sends entries past the cursor until the log end or the first failure.
+async fn deliver(
+    client: &Client,
+    connection: &Data<Mutex<Connection>>,
+    mut webhook: models::Webhook,
+) -> crate::SqlResult<()> {
+    loop {
+        let entries = models::Audit::find_specific_amount_after_id(
+            webhook.cursor,
+            BATCH,
+            &*connection.lock().await,
+        )?;
+
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        for entry in entries {
+            if webhook.matches(&entry) {
+                if let Err(err) = send(client, &webhook, &entry).await {
+                    let conn = connection.lock().await;
+
+                    webhook.fail(&err, &conn)?;
+
+                    if webhook.attempts < models::webhook::MAX_ATTEMPTS {
+                        log::warn!(
+                            "Webhook `{}` failed on entry {} ({} attempts): {}",
+                            webhook.name,
+                            entry.id,
+                            webhook.attempts,
+                            err
+                        );
+
+                        return Ok(());
+                    }
+
+                    log::error!(
+                        "Webhook `{}` skips entry {} after {} attempts: {}",
+                        webhook.name,
+                        entry.id,
+                        webhook.attempts,
+                        err
+                    );
+                }
+            }
+
+            webhook.advance(entry.id, &*connection.lock().await)?;
+        }
+    }
+}
+
+async fn send(
+    client: &Client,
+    webhook: &models::Webhook,
+    entry: &models::Audit,
+) -> Result<(), String> {
+    let body = serde_json::to_vec(entry).map_err(|err| err.to_string())?;
+
+    let res = client
+        .post(&webhook.url)
+        .timeout(TIMEOUT)
+        .header("Content-Type", "application/json")
+        .header("X-Tagz-Event", entry.action.as_str())
+        .header("X-Tagz-Delivery", entry.id.to_string())
+        .header(
+            "X-Tagz-Signature",
+            ["sha256=", &webhook.sign(&body)].concat(),
+        )
+        .send_body(body)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    if res.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("responded with {}", res.status()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, web, App, HttpRequest, HttpResponse, HttpServer};
+    use chrono::Utc;
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex as StdMutex,
+    };
+
+    /// Receiver failing the first `failures` requests.
+    #[derive(Default)]
+    struct StandIn {
+        failures: AtomicU32,
+        /// signature, event and body of every request
+        received: StdMutex<Vec<(String, String, Vec<u8>)>>,
+    }
+
+    async fn receive(
+        stand_in: web::Data<StandIn>,
+        req: HttpRequest,
+        body: web::Bytes,
+    ) -> HttpResponse {
+        let header = |name| {
+            req.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+                .to_owned()
+        };
+
+        stand_in.received.lock().unwrap().push((
+            header("X-Tagz-Signature"),
+            header("X-Tagz-Event"),
+            body.to_vec(),
+        ));
+
+        let failures = stand_in.failures.load(Ordering::SeqCst);
+
+        if failures > 0 {
+            stand_in.failures.store(failures - 1, Ordering::SeqCst);
+
+            HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR)
+        } else {
+            HttpResponse::Ok().finish()
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_an_hour() {
+        let delays = (1..=12).map(models::webhook::backoff).collect::<Vec<_>>();
+
+        assert_eq!(
+            delays,
+            [5, 10, 20, 40, 80, 160, 320, 640, 1280, 2560, 3600, 3600]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn signs_and_retries_failed_entry() {
+        let stand_in = web::Data::new(StandIn {
+            failures: AtomicU32::new(1),
+            ..StandIn::default()
+        });
+        let state = web::Data::clone(&stand_in);
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(web::Data::clone(&state))
+                .route("/", web::post().to(receive))
+        })
+        .workers(1)
+        .bind("127.0.0.1:0")
+        .unwrap();
+        let url = format!("http://{}/", server.addrs()[0]);
+        let server = server.run();
+
+        let conn = crate::get_conn(std::path::Path::new(":memory:")).unwrap();
+        let webhook = models::Webhook::create("hook", &url, Some("secret"), "", "", &conn).unwrap();
+        models::Tag::create("cat", "test", &conn).unwrap();
+        let connection = Data::new(Mutex::new(conn));
+        let client = Client::default();
+
+        // first attempt fails and is scheduled after backoff
+        deliver(&client, &connection, webhook).await.unwrap();
+
+        let webhook = models::Webhook::find_by_name("hook", &*connection.lock().await)
+            .unwrap()
+            .unwrap();
+        let delay = (webhook.next_attempt_at.unwrap() - Utc::now().naive_utc()).num_seconds();
+
+        assert_eq!(webhook.attempts, 1);
+        assert_eq!(webhook.cursor, 0);
+        assert!(webhook.last_error.as_deref().unwrap().contains("500"));
+        assert!((3..=5).contains(&delay), "retried after {}s", delay);
+        assert!(models::Webhook::all_due(&*connection.lock().await)
+            .unwrap()
+            .is_empty());
+
+        // retry succeeds and moves the cursor past the entry
+        deliver(&client, &connection, webhook).await.unwrap();
+        server.stop(false).await;
+
+        let webhook = models::Webhook::find_by_name("hook", &*connection.lock().await)
+            .unwrap()
+            .unwrap();
+        let received = stand_in.received.lock().unwrap();
+
+        assert_eq!(webhook.attempts, 0);
+        assert_eq!(webhook.next_attempt_at, None);
+        assert_eq!(webhook.cursor, 1);
+        assert_eq!(received.len(), 2);
+
+        for (signature, event, body) in received.iter() {
+            assert_eq!(signature, &["sha256=", &webhook.sign(body)].concat());
+            assert_eq!(event, "tag.create");
+        }
+    }
+}
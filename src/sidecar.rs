@@ -0,0 +1,664 @@
+//! Tags kept in files next to the tagged ones: XMP sidecars of photo editors
+//! holding keywords in `dc:subject`, and JSON sidecars `<file>.tagz.json`
+//! holding `{"tags": [...]}`.
+//!
+//! XMP is handled textually, only the `subject` bag of the Dublin Core
+//! namespace is read or replaced, whatever its prefix, and the rest of the
+//! document is kept as it is.
+
+use crate::{
+    import::Importer,
+    library::{self, Library},
+};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+pub const JSON_SUFFIX: &str = ".tagz.json";
+
+const DC_NS: &str = "http://purl.org/dc/elements/1.1/";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Xmp,
+    Json,
+}
+
+impl Format {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Format::Xmp => "xmp",
+            Format::Json => "json",
+        }
+    }
+
+    /// Existing sidecar of `file`, or the one to be created. XMP sidecars are
+    /// looked up as `photo.jpg.xmp`, which is created, and `photo.xmp` used
+    /// only if no other file like `photo.raw` could claim it.
+    pub fn path(self, file: &Path) -> PathBuf {
+        match self {
+            Format::Xmp => {
+                let full = append(file, ".xmp");
+                let bare = file.with_extension("xmp");
+
+                if !full.exists() && bare != file && bare.exists() && claimants(&bare) == 1 {
+                    bare
+                } else {
+                    full
+                }
+            }
+            Format::Json => append(file, JSON_SUFFIX),
+        }
+    }
+
+    /// Whether `path` is a sidecar itself rather than a tagged file.
+    pub fn is_sidecar(self, path: &Path) -> bool {
+        match self {
+            Format::Xmp => path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .map_or(false, |ext| ext.eq_ignore_ascii_case("xmp")),
+            Format::Json => path
+                .to_str()
+                .map_or(false, |path| path.ends_with(JSON_SUFFIX)),
+        }
+    }
+
+    /// Tags of `file`, `None` if it has no sidecar.
+    pub fn read(self, file: &Path) -> io::Result<Option<Vec<String>>> {
+        let content = match fs::read_to_string(self.path(file)) {
+            Ok(content) => content,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        Ok(Some(match self {
+            Format::Xmp => xmp::subjects(&content),
+            Format::Json => json::tags(&content)?,
+        }))
+    }
+
+    /// Replaces tags in the sidecar of `file` keeping its other content, the
+    /// sidecar is created only if there are tags.
+    pub fn write<S>(self, file: &Path, tags: &[S]) -> io::Result<()>
+    where
+        S: AsRef<str>,
+    {
+        let path = self.path(file);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => Some(content),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => return Err(err),
+        };
+
+        if content.is_none() && tags.is_empty() {
+            return Ok(());
+        }
+
+        let tags = tags.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+        let content = match self {
+            Format::Xmp => xmp::with_subjects(content.as_deref(), &tags),
+            Format::Json => json::with_tags(content.as_deref(), &tags)?,
+        };
+
+        fs::write(path, content)
+    }
+}
+
+impl FromStr for Format {
+    type Err = ();
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "xmp" => Ok(Format::Xmp),
+            "json" => Ok(Format::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Imports tags of files under `dir` from their sidecars creating missing
+/// files and tags, returns number of files which got new tags.
+/// Unreadable sidecars are skipped.
+pub fn import(dir: &Path, format: Format, library: &mut Library) -> library::Result<usize> {
+    let mut importer = Importer::new(library, false);
+
+    crate::scan::walk(dir, |path| {
+        if format.is_sidecar(path) {
+            return Ok(());
+        }
+
+        match (path.to_str(), format.read(path)) {
+            (Some(name), Ok(Some(tags))) => importer.import(name, &tags)?,
+            (None, _) | (_, Ok(None)) => {}
+            (_, Err(err)) => log::warn!("Cannot read sidecar of {}: {}", path.display(), err),
+        }
+
+        Ok(())
+    })?;
+
+    Ok(importer.report.files())
+}
+
+/// Writes tags of files under `dir` known to the library to their sidecars,
+/// returns number of sidecars written.
+pub fn export(dir: &Path, format: Format, library: &Library) -> library::Result<usize> {
+    let mut written = 0;
+
+    crate::scan::walk(dir, |path| {
+        if format.is_sidecar(path) {
+            return Ok(());
+        }
+
+        let file = match path.to_str().map(|name| library.file(name)) {
+            Some(Ok(file)) => file,
+            Some(Err(library::Error::FileNotFound)) | None => return Ok(()),
+            Some(Err(err)) => return Err(err),
+        };
+
+        let tags = file
+            .tags
+            .iter()
+            .map(|tag| tag.name.as_str())
+            .collect::<Vec<_>>();
+
+        match format.write(path, &tags) {
+            Ok(()) => written += 1,
+            Err(err) => log::warn!("Cannot write sidecar of {}: {}", path.display(), err),
+        }
+
+        Ok(())
+    })?;
+
+    Ok(written)
+}
+
+/// Number of files sharing name with `sidecar` save the extension.
+fn claimants(sidecar: &Path) -> usize {
+    let (dir, stem) = match (sidecar.parent(), sidecar.file_stem()) {
+        (Some(dir), Some(stem)) => (dir, stem),
+        _ => return 0,
+    };
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+
+    fs::read_dir(dir).map_or(0, |entries| {
+        entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.file_stem() == Some(stem) && !Format::Xmp.is_sidecar(path))
+            .count()
+    })
+}
+
+fn append(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+
+    path.into()
+}
+
+mod xmp {
+    use super::DC_NS;
+
+    const DESCRIPTION: &str = "<rdf:Description";
+
+    /// Items of the `subject` bag.
+    pub fn subjects(content: &str) -> Vec<String> {
+        let (start, end) = match block(content, prefix(content).unwrap_or("dc")) {
+            Some(range) => range,
+            None => return Vec::new(),
+        };
+
+        let mut subjects = Vec::new();
+        let mut rest = &content[start..end];
+
+        while let Some(at) = rest.find("<rdf:li") {
+            rest = &rest[at..];
+
+            let open = match rest.find('>') {
+                Some(open) => open,
+                None => break,
+            };
+
+            // `<rdf:li/>` is an empty item
+            if rest[..open].ends_with('/') {
+                rest = &rest[open + 1..];
+                continue;
+            }
+
+            rest = &rest[open + 1..];
+
+            let close = match rest.find("</rdf:li>") {
+                Some(close) => close,
+                None => break,
+            };
+
+            let subject = unescape(&rest[..close]);
+            let subject = subject.trim();
+
+            if !subject.is_empty() {
+                subjects.push(subject.to_owned());
+            }
+
+            rest = &rest[close..];
+        }
+
+        subjects
+    }
+
+    /// Document with the `subject` bag replaced by `subjects`, a minimal
+    /// document is made when there is none.
+    pub fn with_subjects(content: Option<&str>, subjects: &[&str]) -> String {
+        let content = match content {
+            Some(content) if content.contains(DESCRIPTION) => content,
+            _ => {
+                return format!(
+                    "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+                     <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n \
+                     <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n  \
+                     <rdf:Description rdf:about=\"\" xmlns:dc=\"{}\">\n{}  \
+                     </rdf:Description>\n \
+                     </rdf:RDF>\n\
+                     </x:xmpmeta>\n\
+                     <?xpacket end=\"w\"?>\n",
+                    DC_NS,
+                    subject("dc", subjects)
+                )
+            }
+        };
+        let declared = prefix(content);
+        let prefix = declared.unwrap_or("dc");
+        let subject = subject(prefix, subjects);
+
+        if let Some((start, end)) = block(content, prefix) {
+            let start = content[..start].rfind('\n').map_or(start, |at| at + 1);
+            let end = content[end..].find('\n').map_or(end, |at| end + at + 1);
+
+            return [&content[..start], &subject, &content[end..]].concat();
+        }
+
+        // exiftool writes a description per namespace, the one declaring
+        // Dublin Core keeps the bag in scope of its prefix
+        let at = descriptions(content)
+            .find(|&(at, open)| declares(&content[at..open]))
+            .or_else(|| descriptions(content).next());
+        let (at, open) = match at {
+            Some(found) => found,
+            None => return with_subjects(None, subjects),
+        };
+        let empty = content[..open].ends_with('/');
+        let namespace = match declared {
+            Some(_) => String::new(),
+            None => format!(" xmlns:dc=\"{}\"", DC_NS),
+        };
+        let rest = &content[open + 1..];
+        let close = if empty {
+            let indent = &content[content[..at].rfind('\n').map_or(0, |at| at + 1)..at];
+
+            [indent, "</rdf:Description>"].concat()
+        } else {
+            String::new()
+        };
+
+        [
+            &content[..if empty { open - 1 } else { open }],
+            &namespace,
+            ">\n",
+            &subject,
+            &close,
+            if empty {
+                rest
+            } else {
+                rest.trim_start_matches('\n')
+            },
+        ]
+        .concat()
+    }
+
+    /// Prefix bound to the Dublin Core namespace.
+    fn prefix(content: &str) -> Option<&str> {
+        let mut rest = content;
+
+        while let Some(at) = rest.find("xmlns:") {
+            rest = &rest[at + "xmlns:".len()..];
+
+            let eq = rest.find('=')?;
+
+            if is_namespace(&rest[eq + 1..]) {
+                return Some(rest[..eq].trim());
+            }
+        }
+
+        None
+    }
+
+    /// Whether attributes of opening `tag` bind the Dublin Core namespace.
+    fn declares(tag: &str) -> bool {
+        tag.split('=').skip(1).any(is_namespace)
+    }
+
+    /// Whether attribute `value` starts with quoted Dublin Core namespace.
+    fn is_namespace(value: &str) -> bool {
+        let value = value.trim_start();
+
+        ["\"", "'"]
+            .iter()
+            .any(|quote| value.starts_with(&[quote, DC_NS, quote].concat()))
+    }
+
+    /// Start and end of opening tags of descriptions.
+    fn descriptions(content: &str) -> impl Iterator<Item = (usize, usize)> + '_ {
+        content
+            .match_indices(DESCRIPTION)
+            .filter_map(move |(at, _)| Some((at, at + content[at..].find('>')?)))
+    }
+
+    /// Range of the `subject` element.
+    fn block(content: &str, prefix: &str) -> Option<(usize, usize)> {
+        let open = ["<", prefix, ":subject"].concat();
+        let close = ["</", prefix, ":subject>"].concat();
+        let start = content.match_indices(&open).map(|(at, _)| at).find(|&at| {
+            content[at + open.len()..]
+                .chars()
+                .next()
+                .map_or(false, |next| {
+                    next == '>' || next == '/' || next.is_whitespace()
+                })
+        })?;
+        let end = match content[start..].find('>') {
+            // `<dc:subject/>`
+            Some(tag) if content[..start + tag].ends_with('/') => start + tag + 1,
+            _ => start + content[start..].find(&close)? + close.len(),
+        };
+
+        Some((start, end))
+    }
+
+    fn subject(prefix: &str, subjects: &[&str]) -> String {
+        let mut subject = format!("   <{}:subject>\n    <rdf:Bag>\n", prefix);
+
+        for item in subjects {
+            subject.push_str("     <rdf:li>");
+            subject.push_str(&escape(item));
+            subject.push_str("</rdf:li>\n");
+        }
+
+        subject.push_str(&format!("    </rdf:Bag>\n   </{}:subject>\n", prefix));
+        subject
+    }
+
+    fn escape(text: &str) -> String {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    }
+
+    /// Decodes named and numeric entities, unknown ones are kept.
+    fn unescape(text: &str) -> String {
+        let mut unescaped = String::with_capacity(text.len());
+        let mut rest = text;
+
+        while let Some(at) = rest.find('&') {
+            unescaped.push_str(&rest[..at]);
+            rest = &rest[at..];
+
+            match rest
+                .find(';')
+                .and_then(|end| Some((entity(&rest[1..end])?, end)))
+            {
+                Some((ch, end)) => {
+                    unescaped.push(ch);
+                    rest = &rest[end + 1..];
+                }
+                None => {
+                    unescaped.push('&');
+                    rest = &rest[1..];
+                }
+            }
+        }
+
+        unescaped.push_str(rest);
+        unescaped
+    }
+
+    fn entity(name: &str) -> Option<char> {
+        let code = match name {
+            "lt" => return Some('<'),
+            "gt" => return Some('>'),
+            "amp" => return Some('&'),
+            "quot" => return Some('"'),
+            "apos" => return Some('\''),
+            _ if name.starts_with("#x") || name.starts_with("#X") => {
+                u32::from_str_radix(&name[2..], 16).ok()?
+            }
+            _ if name.starts_with('#') => name[1..].parse().ok()?,
+            _ => return None,
+        };
+
+        std::char::from_u32(code)
+    }
+}
+
+mod json {
+    use serde_json::{Map, Value};
+    use std::io;
+
+    pub fn tags(content: &str) -> io::Result<Vec<String>> {
+        #[derive(serde::Deserialize)]
+        struct Sidecar {
+            #[serde(default)]
+            tags: Vec<String>,
+        }
+
+        Ok(serde_json::from_str::<Sidecar>(content)?
+            .tags
+            .into_iter()
+            .map(|tag| tag.trim().to_owned())
+            .filter(|tag| !tag.is_empty())
+            .collect())
+    }
+
+    /// Object with `tags` replaced, other fields are kept.
+    pub fn with_tags(content: Option<&str>, tags: &[&str]) -> io::Result<String> {
+        let mut object = match content {
+            Some(content) => match serde_json::from_str(content)? {
+                Value::Object(object) => object,
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "sidecar is not a JSON object",
+                    ))
+                }
+            },
+            None => Map::new(),
+        };
+
+        object.insert("tags".to_owned(), tags.into());
+
+        Ok(serde_json::to_string_pretty(&Value::Object(object))? + "\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIGHTROOM: &str = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/" x:xmptk="Adobe XMP Core 7.0-c000">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""
+    xmlns:xmp="http://ns.adobe.com/xap/1.0/"
+    xmlns:dc="http://purl.org/dc/elements/1.1/"
+    xmlns:lr="http://ns.adobe.com/lightroom/1.0/"
+   xmp:Rating="3">
+   <dc:subject>
+    <rdf:Bag>
+     <rdf:li>cat</rdf:li>
+     <rdf:li>Tom &amp; Jerry</rdf:li>
+    </rdf:Bag>
+   </dc:subject>
+   <lr:hierarchicalSubject>
+    <rdf:Bag>
+     <rdf:li>animals|cat</rdf:li>
+    </rdf:Bag>
+   </lr:hierarchicalSubject>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>
+"#;
+
+    const EXIFTOOL: &str = r#"<?xpacket begin='' id='W5M0MpCehiHzreSzNTczkc9d'?>
+<x:xmpmeta xmlns:x='adobe:ns:meta/' x:xmptk='Image::ExifTool 12.40'>
+<rdf:RDF xmlns:rdf='http://www.w3.org/1999/02/22-rdf-syntax-ns#'>
+
+ <rdf:Description rdf:about=''
+  xmlns:exif='http://ns.adobe.com/exif/1.0/'>
+  <exif:ExifVersion>0232</exif:ExifVersion>
+ </rdf:Description>
+
+ <rdf:Description rdf:about=''
+  xmlns:purl='http://purl.org/dc/elements/1.1/'>
+  <purl:title>
+   <rdf:Alt>
+    <rdf:li xml:lang='x-default'>Garden</rdf:li>
+   </rdf:Alt>
+  </purl:title>
+ </rdf:Description>
+</rdf:RDF>
+</x:xmpmeta>
+<?xpacket end='w'?>
+"#;
+
+    #[test]
+    fn reads_lightroom_keywords() {
+        assert_eq!(xmp::subjects(LIGHTROOM), ["cat", "Tom & Jerry"]);
+    }
+
+    #[test]
+    fn replaces_existing_subject_keeping_the_rest() {
+        let content = xmp::with_subjects(Some(LIGHTROOM), &["dog"]);
+
+        assert_eq!(xmp::subjects(&content), ["dog"]);
+        assert!(content.contains(r#"xmp:Rating="3">"#));
+        assert!(content.contains("<rdf:li>animals|cat</rdf:li>"));
+        assert_eq!(content.matches("<dc:subject>").count(), 1);
+    }
+
+    #[test]
+    fn adds_subject_with_declared_prefix_to_declaring_description() {
+        assert!(xmp::subjects(EXIFTOOL).is_empty());
+
+        let content = xmp::with_subjects(Some(EXIFTOOL), &["cat", "dog"]);
+        let subject = content.find("<purl:subject>").unwrap();
+
+        assert_eq!(xmp::subjects(&content), ["cat", "dog"]);
+        assert!(!content.contains("xmlns:dc="));
+        assert!(subject > content.find("xmlns:purl=").unwrap());
+        assert!(content.contains("<rdf:li xml:lang='x-default'>Garden</rdf:li>"));
+    }
+
+    #[test]
+    fn fills_empty_bag() {
+        let content = r#"<rdf:Description rdf:about="" xmlns:dc="http://purl.org/dc/elements/1.1/">
+   <dc:subject>
+    <rdf:Bag/>
+   </dc:subject>
+  </rdf:Description>"#;
+
+        assert!(xmp::subjects(content).is_empty());
+        assert_eq!(
+            xmp::subjects(&xmp::with_subjects(Some(content), &["cat"])),
+            ["cat"]
+        );
+        assert!(xmp::subjects(&xmp::with_subjects(Some(content), &[])).is_empty());
+    }
+
+    #[test]
+    fn opens_self_closing_description() {
+        let content = "<rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n  \
+                       <rdf:Description rdf:about=\"\"/>\n</rdf:RDF>\n";
+        let written = xmp::with_subjects(Some(content), &["cat"]);
+
+        assert_eq!(xmp::subjects(&written), ["cat"]);
+        assert!(written.contains(r#"rdf:about="" xmlns:dc="http://purl.org/dc/elements/1.1/">"#));
+        assert!(written.contains("  </rdf:Description>\n</rdf:RDF>"));
+    }
+
+    #[test]
+    fn decodes_and_encodes_entities() {
+        let content = r#"<rdf:Description xmlns:dc="http://purl.org/dc/elements/1.1/">
+   <dc:subject>
+    <rdf:Bag>
+     <rdf:li>fish &amp; chips</rdf:li>
+     <rdf:li>caf&#233;</rdf:li>
+     <rdf:li>&#xA;new&#x20;line&#xa;</rdf:li>
+     <rdf:li>&lt;b&gt; &quot;q&quot; &apos;a&apos;</rdf:li>
+     <rdf:li>&bogus; &#xD800;</rdf:li>
+    </rdf:Bag>
+   </dc:subject>
+  </rdf:Description>"#;
+        let subjects = xmp::subjects(content);
+
+        assert_eq!(
+            subjects,
+            [
+                "fish & chips",
+                "café",
+                "new line",
+                "<b> \"q\" 'a'",
+                "&bogus; &#xD800;"
+            ]
+        );
+
+        let subjects = subjects.iter().map(String::as_str).collect::<Vec<_>>();
+
+        assert_eq!(
+            xmp::subjects(&xmp::with_subjects(Some(content), &subjects)),
+            subjects
+        );
+    }
+
+    #[test]
+    fn makes_document_without_one() {
+        let content = xmp::with_subjects(None, &["cat"]);
+
+        assert!(content.starts_with("<?xpacket begin="));
+        assert_eq!(xmp::subjects(&content), ["cat"]);
+    }
+
+    #[test]
+    fn prefers_sidecar_named_after_whole_file() {
+        let dir = std::env::temp_dir().join(format!("tagz-sidecar-{}", std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir(&dir).unwrap();
+
+        let jpg = dir.join("photo.jpg");
+        let raw = dir.join("photo.raw");
+        let bare = dir.join("photo.xmp");
+        fs::write(&jpg, b"").unwrap();
+
+        // nothing to find, whole name is created
+        assert_eq!(Format::Xmp.path(&jpg), dir.join("photo.jpg.xmp"));
+
+        // bare sidecar of the only claimant
+        fs::write(&bare, b"").unwrap();
+        assert_eq!(Format::Xmp.path(&jpg), bare);
+
+        // shared by two files, so neither takes it
+        fs::write(&raw, b"").unwrap();
+        assert_eq!(Format::Xmp.path(&jpg), dir.join("photo.jpg.xmp"));
+        assert_eq!(Format::Xmp.path(&raw), dir.join("photo.raw.xmp"));
+
+        // whole name wins over bare one
+        fs::remove_file(&raw).unwrap();
+        fs::write(dir.join("photo.jpg.xmp"), b"").unwrap();
+        assert_eq!(Format::Xmp.path(&jpg), dir.join("photo.jpg.xmp"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,304 @@
+//! `user.xdg.tags` extended attribute read by other tools on Linux, it holds
+//! comma separated tags of the file.
+
+use crate::{
+    import::Importer,
+    library::{self, Library},
+    models::File,
+};
+use std::{io, path::Path};
+
+pub const NAME: &str = "user.xdg.tags";
+
+pub fn read(path: &Path) -> io::Result<Vec<String>> {
+    Ok(match sys::get(path)? {
+        Some(value) => String::from_utf8_lossy(&value)
+            .split(',')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(ToOwned::to_owned)
+            .collect(),
+        None => Vec::new(),
+    })
+}
+
+/// Removes the attribute when there are no tags.
+pub fn write<S>(path: &Path, tags: &[S]) -> io::Result<()>
+where
+    S: AsRef<str>,
+{
+    if tags.is_empty() {
+        sys::remove(path)
+    } else {
+        sys::set(
+            path,
+            tags.iter()
+                .map(AsRef::as_ref)
+                .collect::<Vec<_>>()
+                .join(",")
+                .as_bytes(),
+        )
+    }
+}
+
+/// Writes tags of `file` if its name is a path of existing file.
+pub fn store(file: &File) -> io::Result<()> {
+    match existing(&file.name) {
+        Some(path) => write(
+            path,
+            &file
+                .tags
+                .iter()
+                .map(|tag| tag.name.as_str())
+                .collect::<Vec<_>>(),
+        ),
+        None => Ok(()),
+    }
+}
+
+/// Removes the attribute if `name` is a path of existing file.
+pub fn clear(name: &str) -> io::Result<()> {
+    match existing(name) {
+        Some(path) => sys::remove(path),
+        None => Ok(()),
+    }
+}
+
+fn existing(name: &str) -> Option<&Path> {
+    Some(Path::new(name)).filter(|path| path.is_absolute() && path.exists())
+}
+
+/// Imports tags of files under `dir` from their attribute creating missing
+/// files and tags, returns number of files which got new tags.
+/// Unreadable files are skipped.
+pub fn scan(dir: &Path, library: &mut Library) -> library::Result<usize> {
+    let mut importer = Importer::new(library, false);
+
+    crate::scan::walk(dir, |path| {
+        match (path.to_str(), read(path)) {
+            (Some(name), Ok(tags)) => importer.import(name, &tags)?,
+            (None, _) => {}
+            (_, Err(err)) => log::warn!("Cannot read {} of {}: {}", NAME, path.display(), err),
+        }
+
+        Ok(())
+    })?;
+
+    Ok(importer.report.files())
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use super::NAME;
+    use std::{
+        ffi::CString,
+        io,
+        os::{raw::c_void, unix::ffi::OsStrExt},
+        path::Path,
+        ptr,
+    };
+
+    fn cstrings(path: &Path) -> io::Result<(CString, CString)> {
+        Ok((
+            CString::new(path.as_os_str().as_bytes())?,
+            CString::new(NAME)?,
+        ))
+    }
+
+    pub fn get(path: &Path) -> io::Result<Option<Vec<u8>>> {
+        let (path, name) = cstrings(path)?;
+        let size = unsafe { libc::getxattr(path.as_ptr(), name.as_ptr(), ptr::null_mut(), 0) };
+
+        if size < 0 {
+            let err = io::Error::last_os_error();
+
+            return match err.raw_os_error() {
+                Some(libc::ENODATA) => Ok(None),
+                _ => Err(err),
+            };
+        }
+
+        let mut value = vec![0u8; size as usize];
+        let size = unsafe {
+            libc::getxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                value.as_mut_ptr() as *mut c_void,
+                value.len(),
+            )
+        };
+
+        if size < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        value.truncate(size as usize);
+
+        Ok(Some(value))
+    }
+
+    pub fn set(path: &Path, value: &[u8]) -> io::Result<()> {
+        let (path, name) = cstrings(path)?;
+        let res = unsafe {
+            libc::setxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                value.as_ptr() as *const c_void,
+                value.len(),
+                0,
+            )
+        };
+
+        if res < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn remove(path: &Path) -> io::Result<()> {
+        let (path, name) = cstrings(path)?;
+
+        if unsafe { libc::removexattr(path.as_ptr(), name.as_ptr()) } < 0 {
+            let err = io::Error::last_os_error();
+
+            if err.raw_os_error() != Some(libc::ENODATA) {
+                return Err(err);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod sys {
+    use std::{io, path::Path};
+
+    fn unsupported() -> io::Error {
+        io::Error::new(
+            io::ErrorKind::Other,
+            "extended attributes are supported on Linux only",
+        )
+    }
+
+    pub fn get(_path: &Path) -> io::Result<Option<Vec<u8>>> {
+        Err(unsupported())
+    }
+
+    pub fn set(_path: &Path, _value: &[u8]) -> io::Result<()> {
+        Err(unsupported())
+    }
+
+    pub fn remove(_path: &Path) -> io::Result<()> {
+        Err(unsupported())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{library::Session, models::Access};
+    use std::{fs, path::PathBuf};
+
+    /// Empty temporary file, `None` where its filesystem lacks user attributes.
+    fn temp_file(name: &str) -> Option<PathBuf> {
+        let path = std::env::temp_dir().join(format!("tagz-xattr-{}-{}", std::process::id(), name));
+        fs::write(&path, b"").unwrap();
+
+        match sys::set(&path, b"probe") {
+            Ok(()) => Some(path),
+            Err(err) => {
+                eprintln!("Skipped, no {} on {}: {}", NAME, path.display(), err);
+                fs::remove_file(&path).unwrap();
+
+                None
+            }
+        }
+    }
+
+    fn user(id: i32) -> Session {
+        Session {
+            user_id: Some(id),
+            access: Access::User(id),
+            admin: false,
+            actor: "test".to_owned(),
+        }
+    }
+
+    fn tags(path: &Path) -> Vec<String> {
+        let mut tags = read(path).unwrap();
+        tags.sort();
+        tags
+    }
+
+    #[test]
+    fn reads_split_tags_and_writes_them_back() {
+        let path = match temp_file("round-trip") {
+            Some(path) => path,
+            None => return,
+        };
+
+        sys::set(&path, b" cat, ,dog,").unwrap();
+        let read = read(&path).unwrap();
+
+        assert_eq!(read, ["cat", "dog"]);
+
+        write(&path, &read).unwrap();
+
+        assert_eq!(sys::get(&path).unwrap().unwrap(), b"cat,dog");
+
+        write(&path, &[] as &[&str]).unwrap();
+
+        assert_eq!(sys::get(&path).unwrap(), None);
+        assert!(super::read(&path).unwrap().is_empty());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn stores_tags_visible_to_file_owner() {
+        let path = match temp_file("owner") {
+            Some(path) => path,
+            None => return,
+        };
+        let name = path.to_str().unwrap();
+        let mut conn = crate::get_conn(Path::new(":memory:")).unwrap();
+
+        Library::new(&mut conn, Session::local("test"))
+            .create_tag("cat", None, true)
+            .unwrap();
+        Library::new(&mut conn, user(2))
+            .create_tag("theirs", None, false)
+            .unwrap();
+
+        let mut owner = Library::new(&mut conn, user(1)).with_xattr(true);
+        owner.create_tag("mine", None, false).unwrap();
+        let file = owner.create_file(name, &["cat", "mine"], true).unwrap();
+
+        assert_eq!(tags(&path), ["cat", "mine"]);
+
+        // private tag of another user given by an admin
+        Library::new(&mut conn, Session::local("test"))
+            .with_xattr(true)
+            .add_tag(file.id, "theirs")
+            .unwrap();
+
+        assert_eq!(tags(&path), ["cat", "mine"]);
+
+        let mut local = Library::new(&mut conn, Session::local("test")).with_xattr(true);
+        local.rename_tag("cat", "kitten").unwrap();
+
+        assert_eq!(tags(&path), ["kitten", "mine"]);
+
+        local.delete_tag("kitten", true).unwrap();
+
+        assert_eq!(tags(&path), ["mine"]);
+
+        local.delete_file(name).unwrap();
+
+        assert_eq!(sys::get(&path).unwrap(), None);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,391 @@
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use std::{
+    error::Error,
+    io::{self, Read},
+};
+use tagz_client::{Client, ListQuery, NewFile, NewTag, Status};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+#[actix_rt::main]
+async fn main() {
+    let matches = App::new("tagz")
+        .about("Command-line client of TagZ daemon.")
+        .author("Øsystems")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .setting(AppSettings::VersionlessSubcommands)
+        .arg(
+            Arg::with_name("server")
+                .help("Daemon url")
+                .long("server")
+                .env("TAGZ_SERVER")
+                .default_value("http://127.0.0.1:12345")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("token")
+                .help("API token")
+                .long("token")
+                .env("TAGZ_TOKEN")
+                .hide_env_values(true)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("json")
+                .help("Prints JSON instead of tables")
+                .long("json")
+                .global(true),
+        )
+        .subcommand(
+            SubCommand::with_name("tag")
+                .about("Manages tags. `-` in place of names reads them from stdin.")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(SubCommand::with_name("ls").about("Lists tags."))
+                .subcommand(
+                    SubCommand::with_name("add")
+                        .about("Creates tags.")
+                        .arg(Arg::with_name("names").required(true).multiple(true))
+                        .arg(
+                            Arg::with_name("description")
+                                .short("d")
+                                .long("description")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("private")
+                                .help("Visible to you only")
+                                .long("private"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("rm")
+                        .about("Deletes tags (restorable from trash).")
+                        .arg(Arg::with_name("names").required(true).multiple(true))
+                        .arg(
+                            Arg::with_name("force")
+                                .help("Unlinks tags from their files")
+                                .short("f")
+                                .long("force"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("rename")
+                        .about("Renames tag.")
+                        .arg(Arg::with_name("name").required(true))
+                        .arg(Arg::with_name("new_name").required(true)),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("file")
+                .about("Manages files. `-` in place of tags reads them from stdin.")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("add")
+                        .about("Creates file with tags or adds tags to existing one.")
+                        .arg(Arg::with_name("name").required(true))
+                        .arg(Arg::with_name("tags").multiple(true)),
+                )
+                .subcommand(
+                    SubCommand::with_name("rm")
+                        .about("Removes tags from file, or the file itself when no tags given.")
+                        .arg(Arg::with_name("name").required(true))
+                        .arg(Arg::with_name("tags").multiple(true)),
+                )
+                .subcommand(
+                    SubCommand::with_name("show")
+                        .about("Shows file with its tags.")
+                        .arg(Arg::with_name("name").required(true)),
+                )
+                .subcommand(list_args(
+                    SubCommand::with_name("find")
+                        .about("Finds files having all given tags.")
+                        .arg(Arg::with_name("tags").multiple(true)),
+                )),
+        )
+        .subcommand(list_args(
+            SubCommand::with_name("query")
+                .about("Full-text search over file names, tags and their descriptions.")
+                .arg(Arg::with_name("text").required(true).multiple(true))
+                .arg(
+                    Arg::with_name("tags")
+                        .help("Comma separated tags the files must have")
+                        .long("tags")
+                        .takes_value(true),
+                ),
+        ))
+        .get_matches();
+
+    if let Err(err) = run(&matches).await {
+        eprintln!("tagz: {}", err);
+        std::process::exit(1);
+    }
+}
+
+fn list_args<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
+    app.arg(
+        Arg::with_name("any")
+            .help("Files having any of the tags")
+            .long("any"),
+    )
+    .arg(
+        Arg::with_name("page")
+            .long("page")
+            .takes_value(true)
+            .default_value("0"),
+    )
+    .arg(
+        Arg::with_name("sort")
+            .long("sort")
+            .takes_value(true)
+            .possible_values(&["newest", "oldest", "name", "name_desc", "updated"]),
+    )
+}
+
+async fn run(matches: &ArgMatches<'_>) -> Result<()> {
+    let mut client = Client::new(matches.value_of("server").unwrap());
+
+    if let Some(token) = matches.value_of("token") {
+        client = client.with_token(token);
+    }
+
+    let out = Output {
+        json: matches.is_present("json"),
+    };
+
+    match matches.subcommand() {
+        ("tag", Some(matches)) => tag(&client, &out, matches).await,
+        ("file", Some(matches)) => file(&client, &out, matches).await,
+        ("query", Some(matches)) => {
+            let text = matches
+                .values_of("text")
+                .unwrap()
+                .collect::<Vec<_>>()
+                .join(" ");
+            let query = list_query(matches, matches.value_of("tags"), Some(&text))?;
+
+            out.files(&client.files(&query).await?)
+        }
+        _ => unreachable!(),
+    }
+}
+
+async fn tag(client: &Client, out: &Output, matches: &ArgMatches<'_>) -> Result<()> {
+    match matches.subcommand() {
+        ("ls", _) => out.names(&client.tags().await?),
+        ("add", Some(matches)) => {
+            let mut created = Vec::new();
+
+            for name in values(matches, "names")? {
+                created.push(
+                    client
+                        .create_tag(&NewTag {
+                            name: &name,
+                            description: matches.value_of("description"),
+                            shared: if matches.is_present("private") {
+                                Some(false)
+                            } else {
+                                None
+                            },
+                        })
+                        .await?,
+                );
+            }
+
+            out.print(&created, || {
+                created.iter().for_each(|tag| println!("{}", tag.name))
+            })
+        }
+        ("rm", Some(matches)) => {
+            for name in values(matches, "names")? {
+                client
+                    .delete_tag(&name, matches.is_present("force"))
+                    .await
+                    .map_err(|err| match err.status() {
+                        Some(Status::ConfirmationRequired) => {
+                            format!("tag `{}` has files, use --force to unlink them", name).into()
+                        }
+                        _ => Box::<dyn Error>::from(err),
+                    })?;
+            }
+
+            Ok(())
+        }
+        ("rename", Some(matches)) => {
+            let tag = client
+                .rename_tag(
+                    matches.value_of("name").unwrap(),
+                    matches.value_of("new_name").unwrap(),
+                )
+                .await?;
+
+            out.print(&tag, || println!("{}", tag.name))
+        }
+        _ => unreachable!(),
+    }
+}
+
+async fn file(client: &Client, out: &Output, matches: &ArgMatches<'_>) -> Result<()> {
+    match matches.subcommand() {
+        ("add", Some(matches)) => {
+            let name = matches.value_of("name").unwrap();
+            let tags = values(matches, "tags")?;
+
+            match client.file(name).await {
+                Ok(file) => {
+                    for tag in tags.iter().filter(|tag| !file.tags.contains(tag)) {
+                        client.add_tag(file.id, tag).await?;
+                    }
+                }
+                Err(ref err) if err.status() == Some(Status::FileNotFound) => {
+                    client
+                        .create_file(&NewFile {
+                            name,
+                            tags: &tags.iter().map(String::as_str).collect::<Vec<_>>(),
+                            shared: None,
+                        })
+                        .await?;
+                }
+                Err(err) => return Err(err.into()),
+            }
+
+            out.file(&client.file(name).await?)
+        }
+        ("rm", Some(matches)) => {
+            let name = matches.value_of("name").unwrap();
+            let tags = values(matches, "tags")?;
+
+            if tags.is_empty() {
+                client.delete_file(name).await?;
+            } else {
+                let file = client.file(name).await?;
+
+                for tag in tags.iter().filter(|tag| file.tags.contains(tag)) {
+                    client.remove_tag(file.id, tag).await?;
+                }
+            }
+
+            Ok(())
+        }
+        ("show", Some(matches)) => out.file(&client.file(matches.value_of("name").unwrap()).await?),
+        ("find", Some(matches)) => {
+            let tags = values(matches, "tags")?.join(",");
+            let query = list_query(matches, Some(&tags), None)?;
+
+            out.files(&client.files(&query).await?)
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn list_query<'a>(
+    matches: &ArgMatches<'_>,
+    tags: Option<&'a str>,
+    q: Option<&'a str>,
+) -> Result<ListQuery<'a>> {
+    Ok(ListQuery {
+        page: matches.value_of("page").unwrap().parse()?,
+        tags: tags.filter(|tags| !tags.is_empty()),
+        q,
+        exact: Some(!matches.is_present("any")),
+        sort: matches.value_of("sort").map(str::parse).transpose()?,
+    })
+}
+
+/// Values of argument, `-` is replaced with words read from stdin.
+fn values(matches: &ArgMatches<'_>, name: &str) -> Result<Vec<String>> {
+    let mut values = Vec::new();
+
+    for value in matches.values_of(name).into_iter().flatten() {
+        if value == "-" {
+            let mut input = String::new();
+            io::stdin().read_to_string(&mut input)?;
+
+            values.extend(
+                input
+                    .split(|c: char| c.is_whitespace() || c == ',')
+                    .filter(|word| !word.is_empty())
+                    .map(ToOwned::to_owned),
+            );
+        } else {
+            values.push(value.to_owned());
+        }
+    }
+
+    Ok(values)
+}
+
+struct Output {
+    json: bool,
+}
+
+impl Output {
+    fn print<T, F>(&self, value: &T, human: F) -> Result<()>
+    where
+        T: serde::Serialize,
+        F: FnOnce(),
+    {
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(value)?);
+        } else {
+            human();
+        }
+
+        Ok(())
+    }
+
+    fn names(&self, names: &[String]) -> Result<()> {
+        self.print(&names, || {
+            names.iter().for_each(|name| println!("{}", name))
+        })
+    }
+
+    fn file(&self, file: &tagz_client::File) -> Result<()> {
+        self.print(file, || {
+            table(&[
+                vec!["id".to_owned(), file.id.to_string()],
+                vec!["name".to_owned(), file.name.clone()],
+                vec!["tags".to_owned(), file.tags.join(", ")],
+                vec!["created".to_owned(), file.created_at.to_string()],
+                vec!["updated".to_owned(), file.updated_at.to_string()],
+            ])
+        })
+    }
+
+    fn files(&self, files: &[tagz_client::File]) -> Result<()> {
+        self.print(&files, || {
+            let mut rows = vec![vec!["ID".to_owned(), "NAME".to_owned(), "TAGS".to_owned()]];
+            rows.extend(
+                files
+                    .iter()
+                    .map(|file| vec![file.id.to_string(), file.name.clone(), file.tags.join(", ")]),
+            );
+
+            table(&rows)
+        })
+    }
+}
+
+/// Prints rows with columns padded to the widest cell.
+fn table(rows: &[Vec<String>]) {
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let widths = (0..columns)
+        .map(|column| {
+            rows.iter()
+                .filter_map(|row| row.get(column))
+                .map(|cell| cell.chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect::<Vec<_>>();
+
+    for row in rows {
+        let line = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ");
+
+        println!("{}", line.trim_end());
+    }
+}
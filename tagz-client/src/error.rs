@@ -0,0 +1,76 @@
+use serde::Deserialize;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// `status` of server errors which clients may want to handle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Status {
+    TagsNotFound,
+    TagNotFound,
+    TagDuplication,
+    FileNotFound,
+    FileDuplication,
+    RelFileTagNotFound,
+    RelFileTagExists,
+    /// change raced with another one creating the same entry
+    Conflict,
+    ConfirmationRequired,
+    Unauthorized,
+    PermissionDenied,
+    ReadOnly,
+    InsufficientScope,
+    Sql,
+    /// status unknown to this client
+    #[serde(other)]
+    Other,
+}
+
+/// Body of `{"Err": ...}` responses.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ServiceError {
+    #[serde(skip)]
+    pub http_status: u16,
+
+    pub status: String,
+    pub message: String,
+    pub details: Option<serde_json::Value>,
+}
+
+impl ServiceError {
+    pub fn kind(&self) -> Status {
+        serde_json::from_value(serde_json::Value::String(self.status.clone()))
+            .unwrap_or(Status::Other)
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// server answered with an error
+    Service(ServiceError),
+    /// request could not be sent or response could not be read
+    Request(String),
+    /// response is not what this client expects
+    Decode(String),
+}
+
+impl Error {
+    /// Status of server error, `None` for transport failures.
+    pub fn status(&self) -> Option<Status> {
+        match self {
+            Error::Service(err) => Some(err.kind()),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        match self {
+            Error::Service(err) => write!(fmt, "{}: {}", err.status, err.message),
+            Error::Request(err) => write!(fmt, "request failed: {}", err),
+            Error::Decode(err) => write!(fmt, "unexpected response: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
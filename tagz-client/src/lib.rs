@@ -0,0 +1,264 @@
+//! Async client of TagZ REST API (`/api/v1`), meant to run within actix system.
+//!
+//! ```ignore
+//! let client = tagz_client::Client::new("http://127.0.0.1:12345").with_token(token);
+//! let file = client.create_file(&NewFile { name: "cat.png", tags: &["cat"], shared: None }).await?;
+//! ```
+
+pub mod error;
+pub mod types;
+
+pub use error::{Error, ServiceError, Status};
+pub use types::*;
+
+use awc::{http::StatusCode, ClientRequest};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Deserialize)]
+enum Envelope<T> {
+    Ok(T),
+    Err(ServiceError),
+}
+
+#[derive(Serialize)]
+struct TagsQuery<'a> {
+    tags: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    limit: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct Rename<'a> {
+    name: &'a str,
+}
+
+#[derive(Serialize)]
+struct LimitQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    limit: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct FacetsQuery<'a> {
+    #[serde(flatten)]
+    list: &'a ListQuery<'a>,
+    facets: bool,
+}
+
+#[derive(Clone)]
+pub struct Client {
+    http: awc::Client,
+    base: String,
+    token: Option<String>,
+}
+
+/// Escapes path segment, names of tags and files may contain anything.
+fn segment(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+impl Client {
+    /// `base` is the server root, e.g. `http://127.0.0.1:12345`.
+    pub fn new<B: Into<String>>(base: B) -> Self {
+        Self {
+            http: awc::Client::default(),
+            base: base.into().trim_end_matches('/').to_owned(),
+            token: None,
+        }
+    }
+
+    pub fn with_token<T: Into<String>>(mut self, token: T) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    fn url(&self, path: &str) -> String {
+        [&self.base, "/api/v1/", path].concat()
+    }
+
+    fn authorize(&self, req: ClientRequest) -> ClientRequest {
+        match &self.token {
+            Some(token) => req.bearer_auth(token),
+            None => req,
+        }
+    }
+
+    fn get(&self, path: &str) -> ClientRequest {
+        self.authorize(self.http.get(self.url(path)))
+    }
+
+    fn post(&self, path: &str) -> ClientRequest {
+        self.authorize(self.http.post(self.url(path)))
+    }
+
+    fn put(&self, path: &str) -> ClientRequest {
+        self.authorize(self.http.put(self.url(path)))
+    }
+
+    fn delete(&self, path: &str) -> ClientRequest {
+        self.authorize(self.http.delete(self.url(path)))
+    }
+
+    fn query<Q: Serialize>(req: ClientRequest, query: &Q) -> Result<ClientRequest> {
+        req.query(query)
+            .map_err(|err| Error::Request(err.to_string()))
+    }
+
+    /// Sends request and decodes `{"Ok": T}`, `None` body means no content is expected.
+    async fn send<T, B>(req: ClientRequest, body: Option<&B>) -> Result<T>
+    where
+        T: DeserializeOwned,
+        B: Serialize,
+    {
+        let res = match body {
+            Some(body) => req.send_json(body).await,
+            None => req.send().await,
+        };
+        let mut res = res.map_err(|err| Error::Request(err.to_string()))?;
+        let status = res.status();
+        let bytes = res
+            .body()
+            .limit(64 * 1024 * 1024)
+            .await
+            .map_err(|err| Error::Request(err.to_string()))?;
+
+        if status == StatusCode::NO_CONTENT {
+            return serde_json::from_value(serde_json::Value::Null)
+                .map_err(|err| Error::Decode(err.to_string()));
+        }
+
+        match serde_json::from_slice::<Envelope<T>>(&bytes) {
+            Ok(Envelope::Ok(value)) => Ok(value),
+            Ok(Envelope::Err(mut err)) => {
+                err.http_status = status.as_u16();
+
+                Err(Error::Service(err))
+            }
+            Err(err) => Err(Error::Decode(format!(
+                "{} ({}): {}",
+                status,
+                err,
+                String::from_utf8_lossy(&bytes)
+            ))),
+        }
+    }
+
+    //--- tags
+    /// Names of visible tags.
+    pub async fn tags(&self) -> Result<Vec<String>> {
+        Self::send(self.get("tags"), None::<&()>).await
+    }
+
+    pub async fn create_tag(&self, tag: &NewTag<'_>) -> Result<Tag> {
+        Self::send(self.post("tags"), Some(tag)).await
+    }
+
+    pub async fn rename_tag(&self, name: &str, new_name: &str) -> Result<Tag> {
+        Self::send(
+            self.put(&["tags/", &segment(name)].concat()),
+            Some(&Rename { name: new_name }),
+        )
+        .await
+    }
+
+    /// Tag having files requires `confirm`, its files are unlinked then.
+    pub async fn delete_tag(&self, name: &str, confirm: bool) -> Result<()> {
+        let req = self.delete(&["tags/", &segment(name)].concat());
+        let req = if confirm {
+            Self::query(req, &[("confirm", true)])?
+        } else {
+            req
+        };
+
+        Self::send(req, None::<&()>).await
+    }
+
+    pub async fn related_tags(&self, tags: &[&str], limit: Option<u32>) -> Result<Vec<RelatedTag>> {
+        let req = Self::query(
+            self.get("tags/related"),
+            &TagsQuery {
+                tags: &tags.join(","),
+                limit,
+            },
+        )?;
+
+        Self::send(req, None::<&()>).await
+    }
+
+    pub async fn search_tags(&self, q: &str) -> Result<Vec<Tag>> {
+        Self::send(
+            Self::query(self.get("tags/search"), &[("q", q)])?,
+            None::<&()>,
+        )
+        .await
+    }
+
+    //--- files
+    pub async fn files(&self, query: &ListQuery<'_>) -> Result<Vec<File>> {
+        Self::send(Self::query(self.get("files"), query)?, None::<&()>).await
+    }
+
+    /// Listing along with total and tag counts across all pages.
+    pub async fn files_with_facets(&self, query: &ListQuery<'_>) -> Result<FacetedList> {
+        let req = Self::query(
+            self.get("files"),
+            &FacetsQuery {
+                list: query,
+                facets: true,
+            },
+        )?;
+
+        Self::send(req, None::<&()>).await
+    }
+
+    pub async fn create_file(&self, file: &NewFile<'_>) -> Result<File> {
+        Self::send(self.post("files"), Some(file)).await
+    }
+
+    pub async fn file(&self, name: &str) -> Result<File> {
+        Self::send(self.get(&["files/", &segment(name)].concat()), None::<&()>).await
+    }
+
+    pub async fn delete_file(&self, name: &str) -> Result<()> {
+        Self::send(
+            self.delete(&["files/", &segment(name)].concat()),
+            None::<&()>,
+        )
+        .await
+    }
+
+    pub async fn add_tag(&self, file_id: i32, tag: &str) -> Result<()> {
+        let path = ["files/", &file_id.to_string(), "/", &segment(tag)].concat();
+
+        Self::send(self.post(&path), None::<&()>).await
+    }
+
+    pub async fn remove_tag(&self, file_id: i32, tag: &str) -> Result<()> {
+        let path = ["files/", &file_id.to_string(), "/", &segment(tag)].concat();
+
+        Self::send(self.delete(&path), None::<&()>).await
+    }
+
+    /// Tags to suggest for a file, excluding its own.
+    pub async fn related_to_file(
+        &self,
+        file_id: i32,
+        limit: Option<u32>,
+    ) -> Result<Vec<RelatedTag>> {
+        let req = Self::query(
+            self.get(&["files/", &file_id.to_string(), "/related"].concat()),
+            &LimitQuery { limit },
+        )?;
+
+        Self::send(req, None::<&()>).await
+    }
+}
@@ -0,0 +1,37 @@
+//! Shapes of requests of `api/v1`, responses are shared with the server
+//! through `tagz-types`.
+
+use serde::Serialize;
+
+pub use tagz_types::{Facet, FacetedList, File, RelatedTag, Sort, Tag, UnknownSort};
+
+#[derive(Clone, Debug, Serialize)]
+pub struct NewFile<'a> {
+    pub name: &'a str,
+    pub tags: &'a [&'a str],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shared: Option<bool>,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct NewTag<'a> {
+    pub name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shared: Option<bool>,
+}
+
+/// Query of file listing, `tags` are comma separated.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ListQuery<'a> {
+    pub page: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub q: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exact: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<Sort>,
+}
@@ -0,0 +1,130 @@
+//! Client against a server running in the same process.
+
+use std::{net::TcpListener, path::Path, time::Duration};
+use tagz_client::{Client, Error, ListQuery, NewFile, NewTag, Status};
+
+/// Serves a fresh in-memory database on a free loopback port.
+async fn start() -> Client {
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let target = format!("127.0.0.1:{}", port);
+    let cfg = tagz::AppConfig {
+        http_target: target.as_str().into(),
+        read_only: false,
+        tls_cert: None,
+        tls_key: None,
+        socket_mode: 0o660,
+        trash_days: 0,
+        xattr: false,
+        insecure_no_auth: false,
+    };
+    let conn = tagz::get_conn(Path::new(":memory:")).unwrap();
+
+    actix_rt::spawn(async move { tagz::serv::run(conn, cfg).await.unwrap() });
+
+    let client = Client::new(["http://", &target].concat());
+
+    // server binds once the spawned future is polled
+    for _ in 0..50 {
+        if client.tags().await.is_ok() {
+            return client;
+        }
+
+        actix_rt::time::delay_for(Duration::from_millis(20)).await;
+    }
+
+    panic!("server at {} did not start", target);
+}
+
+fn service_error(err: Error) -> (Option<Status>, u16) {
+    match err {
+        Error::Service(ref service) => (err.status(), service.http_status),
+        err => panic!("expected service error, got {}", err),
+    }
+}
+
+#[actix_rt::test]
+async fn creates_lists_and_deletes() {
+    let client = start().await;
+
+    let tag = client
+        .create_tag(&NewTag {
+            name: "cat",
+            description: Some("felines"),
+            ..NewTag::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(tag.name, "cat");
+    assert_eq!(tag.description.as_deref(), Some("felines"));
+
+    let file = client
+        .create_file(&NewFile {
+            name: "cat.png",
+            tags: &["cat"],
+            shared: None,
+        })
+        .await
+        .unwrap();
+    assert_eq!(file.tags, ["cat"]);
+
+    let files = client.files(&ListQuery::default()).await.unwrap();
+    assert_eq!(
+        files.iter().map(|file| file.name.as_str()).collect::<Vec<_>>(),
+        ["cat.png"]
+    );
+    assert_eq!(client.tags().await.unwrap(), ["cat"]);
+
+    let faceted = client
+        .files_with_facets(&ListQuery::default())
+        .await
+        .unwrap();
+    assert_eq!(faceted.total, 1);
+    assert_eq!(faceted.facets[0].name, "cat");
+
+    client.delete_file("cat.png").await.unwrap();
+    assert_eq!(
+        service_error(client.file("cat.png").await.unwrap_err()),
+        (Some(Status::FileNotFound), 404)
+    );
+
+    client.delete_tag("cat", false).await.unwrap();
+    assert!(client.tags().await.unwrap().is_empty());
+}
+
+#[actix_rt::test]
+async fn decodes_error_statuses() {
+    let client = start().await;
+
+    let err = client
+        .create_file(&NewFile {
+            name: "dog.png",
+            tags: &["dog"],
+            shared: None,
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(service_error(err), (Some(Status::TagsNotFound), 400));
+
+    client
+        .create_tag(&NewTag {
+            name: "dog",
+            ..NewTag::default()
+        })
+        .await
+        .unwrap();
+    let file = client
+        .create_file(&NewFile {
+            name: "dog.png",
+            tags: &["dog"],
+            shared: None,
+        })
+        .await
+        .unwrap();
+
+    let err = client.add_tag(file.id, "dog").await.unwrap_err();
+    assert_eq!(service_error(err), (Some(Status::RelFileTagExists), 409));
+}
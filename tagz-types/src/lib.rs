@@ -0,0 +1,138 @@
+//! Shapes of responses of `api/v1` and values taken by requests. The server
+//! serializes its models through them, so clients decoding these types read
+//! what the server writes.
+//!
+//! The `rusqlite` feature lets the server store values in its database.
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    fmt::{Display, Formatter, Result as FmtResult},
+    str::FromStr,
+};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct File {
+    pub id: i32,
+    pub name: String,
+    pub owner_id: Option<i32>,
+    pub shared: bool,
+    /// names of visible tags
+    pub tags: Vec<String>,
+    pub updated_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Tag {
+    pub id: i32,
+    pub name: String,
+    pub description: Option<String>,
+    pub owner_id: Option<i32>,
+    pub shared: bool,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RelatedTag {
+    pub name: String,
+    pub score: f64,
+    pub occurrences: i64,
+}
+
+/// Tag along with number of matched files carrying it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Facet {
+    pub name: String,
+    pub count: i64,
+}
+
+/// Page of files along with total and facets across all pages.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FacetedList {
+    pub files: Vec<File>,
+    pub total: i64,
+    pub facets: Vec<Facet>,
+}
+
+/// Order of listed files.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sort {
+    Newest,
+    Oldest,
+    Name,
+    NameDesc,
+    Updated,
+}
+
+impl Sort {
+    pub const ALL: &'static [Sort] = &[
+        Sort::Newest,
+        Sort::Oldest,
+        Sort::Name,
+        Sort::NameDesc,
+        Sort::Updated,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Sort::Newest => "newest",
+            Sort::Oldest => "oldest",
+            Sort::Name => "name",
+            Sort::NameDesc => "name_desc",
+            Sort::Updated => "updated",
+        }
+    }
+}
+
+impl Default for Sort {
+    fn default() -> Self {
+        Sort::Newest
+    }
+}
+
+/// Name matching none of [`Sort::ALL`].
+#[derive(Debug)]
+pub struct UnknownSort(pub String);
+
+impl Display for UnknownSort {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        write!(fmt, "unknown sort `{}`", self.0)
+    }
+}
+
+impl Error for UnknownSort {}
+
+impl FromStr for Sort {
+    type Err = UnknownSort;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Sort::ALL
+            .iter()
+            .copied()
+            .find(|sort| sort.as_str() == s)
+            .ok_or_else(|| UnknownSort(s.to_owned()))
+    }
+}
+
+#[cfg(feature = "rusqlite")]
+mod sql {
+    use super::Sort;
+    use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+
+    impl ToSql for Sort {
+        fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+            Ok(ToSqlOutput::from(self.as_str()))
+        }
+    }
+
+    impl FromSql for Sort {
+        fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+            value
+                .as_str()
+                .and_then(|s| s.parse().map_err(|_| FromSqlError::InvalidType))
+        }
+    }
+}